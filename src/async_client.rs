@@ -1,102 +1,154 @@
-use futures::future::BoxFuture;
 use futures::prelude::*;
-use futures::{future::try_join_all, try_join};
+use futures::stream::{self, BoxStream};
+use futures::try_join;
 use reqwest::{header, Client as HttpClient, StatusCode, Url};
 use serde::de::DeserializeOwned;
 
-use std::collections::VecDeque;
+use std::sync::Arc;
 
 use super::Error;
+use crate::cache::{Endpoint, Lookup, ResponseCache};
+use crate::compare::{compared_version, ComparedCrate, CrateComparison};
+use crate::downloads::DownloadAnalytics;
 use crate::error::JsonDecodeError;
+use crate::pagination::{Page, PageCursor, Paginated};
+use crate::rate_limit::{FixedIntervalLimiter, RateLimiter};
 use crate::types::*;
 
 /// Asynchronous client for the crates.io API.
 #[derive(Clone)]
 pub struct Client {
     client: HttpClient,
-    rate_limit: std::time::Duration,
-    last_request_time: std::sync::Arc<tokio::sync::Mutex<Option<tokio::time::Instant>>>,
+    rate_limiter: Arc<dyn RateLimiter>,
     base_url: Url,
+    cache: Option<ResponseCache>,
 }
 
+/// Stream over all crates matching a [`CratesQuery`].
+///
+/// Created by [`Client::crates_stream`].
 pub struct CrateStream {
-    client: Client,
-    filter: CratesQuery,
-
-    closed: bool,
-    items: VecDeque<Crate>,
-    next_page_fetch: Option<BoxFuture<'static, Result<CratesPage, Error>>>,
+    query: CratesQuery,
+    inner: Paginated<Crate>,
 }
 
 impl CrateStream {
-    fn new(client: Client, filter: CratesQuery) -> Self {
-        Self {
-            client,
-            filter,
-            closed: false,
-            items: VecDeque::new(),
-            next_page_fetch: None,
+    /// The current position, for resuming the crawl later with
+    /// [`Client::crates_stream_resume`].
+    pub fn checkpoint(&self) -> CrateStreamCheckpoint {
+        CrateStreamCheckpoint {
+            query: self.query.clone(),
+            position: self.inner.checkpoint(),
         }
     }
+
+    /// Cursor of the most recently fetched page, if any.
+    pub fn cursor(&self) -> Option<&PageCursor> {
+        self.inner.cursor()
+    }
+
+    /// Total number of matching crates, known after the first page has been
+    /// fetched.
+    pub fn total(&self) -> Option<u64> {
+        self.inner.total()
+    }
 }
 
 impl futures::stream::Stream for CrateStream {
     type Item = Result<Crate, Error>;
 
     fn poll_next(
-        self: std::pin::Pin<&mut Self>,
+        mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        let inner = self.get_mut();
+        self.inner.poll_next_unpin(cx)
+    }
+}
 
-        if inner.closed {
-            return std::task::Poll::Ready(None);
-        }
+/// Options for [`Client::full_crate_with_options`] and
+/// [`Client::full_crate_stream`].
+#[derive(Clone)]
+pub struct FullCrateOptions {
+    all_versions: bool,
+    max_in_flight: usize,
+    progress: Option<Arc<dyn Fn(FullCrateProgress) + Send + Sync>>,
+}
 
-        if let Some(krate) = inner.items.pop_front() {
-            return std::task::Poll::Ready(Some(Ok(krate)));
+impl FullCrateOptions {
+    /// Create the default options: only the latest version is fetched, with
+    /// at most 4 versions in flight.
+    pub fn new() -> Self {
+        Self {
+            all_versions: false,
+            max_in_flight: 4,
+            progress: None,
         }
+    }
 
-        if let Some(mut fut) = inner.next_page_fetch.take() {
-            return match fut.poll_unpin(cx) {
-                std::task::Poll::Ready(res) => match res {
-                    Ok(page) if page.crates.is_empty() => {
-                        inner.closed = true;
-                        std::task::Poll::Ready(None)
-                    }
-                    Ok(page) => {
-                        let mut iter = page.crates.into_iter();
-                        let next = iter.next();
-                        inner.items.extend(iter);
-
-                        std::task::Poll::Ready(next.map(Ok))
-                    }
-                    Err(err) => {
-                        inner.closed = true;
-                        std::task::Poll::Ready(Some(Err(err)))
-                    }
-                },
-                std::task::Poll::Pending => {
-                    inner.next_page_fetch = Some(fut);
-                    std::task::Poll::Pending
-                }
-            };
-        }
+    /// Fetch details for all versions instead of only the latest one.
+    #[must_use]
+    pub fn all_versions(mut self, all_versions: bool) -> Self {
+        self.all_versions = all_versions;
+        self
+    }
 
-        let filter = inner.filter.clone();
-        inner.filter.page += 1;
+    /// Set the maximum number of versions fetched concurrently.
+    ///
+    /// Each version requires two requests, which are still subject to the
+    /// client's rate limit.
+    #[must_use]
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
 
-        let c = inner.client.clone();
-        let mut f = Box::pin(async move { c.crates(filter).await });
-        assert!(matches!(f.poll_unpin(cx), std::task::Poll::Pending));
-        inner.next_page_fetch = Some(f);
+    /// Report progress to the given callback.
+    #[must_use]
+    pub fn on_progress(
+        mut self,
+        callback: impl Fn(FullCrateProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+}
 
-        cx.waker().wake_by_ref();
+impl Default for FullCrateOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        std::task::Poll::Pending
+impl std::fmt::Debug for FullCrateOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FullCrateOptions")
+            .field("all_versions", &self.all_versions)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("progress", &self.progress.is_some())
+            .finish()
     }
 }
 
+/// Progress events reported while fetching version details.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum FullCrateProgress {
+    /// The crate was fetched and `total` versions will be fetched.
+    Started {
+        /// Number of versions to fetch.
+        total: usize,
+    },
+    /// Details for a version have been fetched.
+    VersionFetched {
+        /// The version number.
+        version: String,
+        /// Number of versions fetched so far.
+        completed: usize,
+        /// Number of versions to fetch.
+        total: usize,
+    },
+}
+
 impl Client {
     /// Instantiate a new client.
     ///
@@ -105,9 +157,11 @@ impl Client {
     /// To respect the offical [Crawler Policy](https://crates.io/policies#crawlers),
     /// you must specify both a descriptive user agent and a rate limit interval.
     ///
-    /// At most one request will be executed in the specified duration.
+    /// Every request reserves the next free slot of the rate limiter, so at
+    /// most one request will be started in the specified duration.
+    /// A request that is cancelled while waiting for its slot still uses it up.
     /// The guidelines suggest 1 per second or less.
-    /// (Only one request is executed concurrenly, even if the given Duration is 0).
+    /// Use [`Client::with_rate_limiter`] for other rate limiting strategies.
     ///
     /// Example user agent: `"my_bot (my_bot.com/info)"` or `"my_bot (help@my_bot.com)"`.
     ///
@@ -143,70 +197,69 @@ impl Client {
     /// To respect the offical [Crawler Policy](https://crates.io/policies#crawlers),
     /// you must specify both a descriptive user agent and a rate limit interval.
     ///
-    /// At most one request will be executed in the specified duration.
+    /// Every request reserves the next free slot of the rate limiter, so at
+    /// most one request will be started in the specified duration.
+    /// A request that is cancelled while waiting for its slot still uses it up.
     /// The guidelines suggest 1 per second or less.
-    /// (Only one request is executed concurrenly, even if the given Duration is 0).
+    /// Use [`Client::with_rate_limiter`] for other rate limiting strategies.
     pub fn with_http_client(client: HttpClient, rate_limit: std::time::Duration) -> Self {
-        let limiter = std::sync::Arc::new(tokio::sync::Mutex::new(None));
-
         Self {
-            rate_limit,
-            last_request_time: limiter,
+            rate_limiter: Arc::new(FixedIntervalLimiter::new(rate_limit)),
             client,
             base_url: Url::parse("https://crates.io/api/v1/").unwrap(),
+            cache: None,
         }
     }
 
-    async fn get<T: DeserializeOwned>(&self, url: &Url) -> Result<T, Error> {
-        let mut lock = self.last_request_time.clone().lock_owned().await;
+    /// Replace the rate limiter.
+    ///
+    /// The limiter can be shared with other clients, see [`RateLimiter`].
+    #[must_use]
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
 
-        if let Some(last_request_time) = lock.take() {
-            if last_request_time.elapsed() < self.rate_limit {
-                tokio::time::sleep(self.rate_limit - last_request_time.elapsed()).await;
-            }
-        }
+    /// Cache responses and revalidate them with conditional requests.
+    ///
+    /// See [`ResponseCache`] for details.
+    #[must_use]
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 
-        let time = tokio::time::Instant::now();
-        let res = self.client.get(url.clone()).send().await?;
-
-        if !res.status().is_success() {
-            let err = match res.status() {
-                StatusCode::NOT_FOUND => Error::NotFound(super::error::NotFoundError {
-                    url: url.to_string(),
-                }),
-                StatusCode::FORBIDDEN => {
-                    let reason = res.text().await.unwrap_or_default();
-                    Error::PermissionDenied(super::error::PermissionDeniedError { reason })
-                }
-                _ => Error::from(res.error_for_status().unwrap_err()),
-            };
+    async fn get<T: DeserializeOwned>(&self, url: &Url, endpoint: Endpoint) -> Result<T, Error> {
+        let cached = match &self.cache {
+            Some(cache) => match cache.lookup(url, endpoint)? {
+                Lookup::Fresh(value) => return Ok(value),
+                Lookup::Send(cached) => cached,
+            },
+            None => None,
+        };
+
+        tokio::time::sleep(self.rate_limiter.reserve()).await;
 
-            return Err(err);
+        let mut req = self.client.get(url.clone());
+        if let Some(entry) = &cached {
+            req = req.headers(ResponseCache::conditional_headers(entry));
         }
+        let res = req.send().await?;
 
+        let status = res.status();
+        let headers = res.headers().clone();
         let content = res.text().await?;
 
-        // Free up the lock
-        (*lock) = Some(time);
-
-        // First, check for api errors.
-
-        if let Ok(errors) = serde_json::from_str::<ApiErrors>(&content) {
-            return Err(Error::Api(errors));
+        match &self.cache {
+            Some(cache) => cache.response(url, cached, status, &headers, content),
+            None => parse_response(url, status, &headers, &content),
         }
-
-        let jd = &mut serde_json::Deserializer::from_str(&content);
-        serde_path_to_error::deserialize::<_, T>(jd).map_err(|err| {
-            Error::JsonDecode(JsonDecodeError {
-                message: format!("Could not decode JSON: {err} (path: {})", err.path()),
-            })
-        })
     }
 
     /// Retrieve a summary containing crates.io wide information.
     pub async fn summary(&self) -> Result<Summary, Error> {
         let url = self.base_url.join("summary").unwrap();
-        self.get(&url).await
+        self.get(&url, Endpoint::Summary).await
     }
 
     /// Retrieve information of a crate.
@@ -215,19 +268,58 @@ impl Client {
     pub async fn get_crate(&self, crate_name: &str) -> Result<CrateResponse, Error> {
         let url = build_crate_url(&self.base_url, crate_name)?;
 
-        self.get(&url).await
+        self.get(&url, Endpoint::Crate).await
     }
 
     /// Retrieve download stats for a crate.
     pub async fn crate_downloads(&self, crate_name: &str) -> Result<CrateDownloads, Error> {
         let url = build_crate_downloads_url(&self.base_url, crate_name)?;
-        self.get(&url).await
+        self.get(&url, Endpoint::CrateDownloads).await
+    }
+
+    /// Compare crates side by side.
+    ///
+    /// Loads the crate, its owners, its reverse dependency count and the
+    /// dependencies of its latest stable version for each crate.
+    pub async fn compare_crates(&self, names: &[&str]) -> Result<CrateComparison, Error> {
+        let now = crate::compare::now();
+        let crates = names.iter().map(|name| async move {
+            let krate = self.get_crate(name).await?;
+            let (owners, reverse_dependencies, dependencies) = try_join!(
+                self.crate_owners(name),
+                self.crate_reverse_dependency_count(name),
+                self.crate_dependencies(name, compared_version(&krate))
+            )?;
+            Ok::<_, Error>(ComparedCrate::new(
+                &krate,
+                &owners,
+                &dependencies,
+                reverse_dependencies,
+                now,
+            ))
+        });
+        let crates = future::try_join_all(crates).await?;
+        Ok(CrateComparison { crates })
+    }
+
+    /// Retrieve download stats for a crate, joined with its versions.
+    pub async fn crate_download_analytics(
+        &self,
+        crate_name: &str,
+    ) -> Result<DownloadAnalytics, Error> {
+        let (downloads, versions) = try_join!(
+            self.crate_downloads(crate_name),
+            self.crate_versions(crate_name)
+        )?;
+        Ok(DownloadAnalytics::new(&downloads, &versions))
     }
 
     /// Retrieve the owners of a crate.
     pub async fn crate_owners(&self, name: &str) -> Result<Vec<User>, Error> {
         let url = build_crate_owners_url(&self.base_url, name)?;
-        self.get::<Owners>(&url).await.map(|data| data.users)
+        self.get::<Owners>(&url, Endpoint::CrateOwners)
+            .await
+            .map(|data| data.users)
     }
 
     /// Get a single page of reverse dependencies.
@@ -237,18 +329,51 @@ impl Client {
         &self,
         crate_name: &str,
         page: u64,
+    ) -> Result<ReverseDependencies, Error> {
+        let mut query = ReverseDependenciesQuery::default();
+        query.set_page(page);
+        self.crate_reverse_dependencies_page_with_query(crate_name, &query)
+            .await
+    }
+
+    /// Get the page of reverse dependencies selected by the query.
+    ///
+    /// The filters of the query only apply to this page, so a dependent can
+    /// appear with an older version if its latest version is on another
+    /// page. `meta.total` is the number of dependents reported by the API.
+    pub async fn crate_reverse_dependencies_page_with_query(
+        &self,
+        crate_name: &str,
+        query: &ReverseDependenciesQuery,
+    ) -> Result<ReverseDependencies, Error> {
+        let mut res = self
+            .fetch_reverse_dependencies(crate_name, query.page, query.per_page)
+            .await?;
+        res.dependencies = query.filter_page(res.dependencies);
+        Ok(res)
+    }
+
+    async fn fetch_reverse_dependencies(
+        &self,
+        crate_name: &str,
+        page: u64,
+        per_page: u64,
     ) -> Result<ReverseDependencies, Error> {
         // If page is zero, bump it to 1.
         let page = page.max(1);
 
-        let url = build_crate_reverse_deps_url(&self.base_url, crate_name, page)?;
-        let page = self.get::<ReverseDependenciesAsReceived>(&url).await?;
+        let url = build_crate_reverse_deps_url(&self.base_url, crate_name, page, per_page)?;
+        let page = self
+            .get::<ReverseDependenciesAsReceived>(&url, Endpoint::ReverseDependencies)
+            .await?;
 
         let mut deps = ReverseDependencies {
             dependencies: Vec::new(),
-            meta: Meta { total: 0 },
+            meta: Meta {
+                total: page.meta.total,
+                ..Meta::default()
+            },
         };
-        deps.meta.total = page.meta.total;
         deps.extend(page);
         Ok(deps)
     }
@@ -258,27 +383,81 @@ impl Client {
     /// Note: Since the reverse dependency endpoint requires pagination, this
     /// will result in multiple requests if the crate has more than 100 reverse
     /// dependencies.
+    /// Consider [`Client::crate_reverse_dependencies_stream`] for crates with
+    /// many dependents.
     pub async fn crate_reverse_dependencies(
         &self,
         crate_name: &str,
     ) -> Result<ReverseDependencies, Error> {
-        let mut deps = ReverseDependencies {
-            dependencies: Vec::new(),
-            meta: Meta { total: 0 },
-        };
+        self.crate_reverse_dependencies_with_query(crate_name, ReverseDependenciesQuery::default())
+            .await
+    }
 
-        for page_number in 1.. {
-            let page = self
-                .crate_reverse_dependencies_page(crate_name, page_number)
-                .await?;
-            if page.dependencies.is_empty() {
-                break;
-            }
-            deps.dependencies.extend(page.dependencies);
-            deps.meta.total = page.meta.total;
-        }
+    /// Load all reverse dependencies of a crate matching the query.
+    ///
+    /// Loading starts at the page of the query. `meta.total` is the number of
+    /// dependents reported by the API, before any filters were applied.
+    ///
+    /// ```rust
+    /// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = crates_io_api::AsyncClient::new("", std::time::Duration::from_secs(1))?;
+    /// use crates_io_api::ReverseDependenciesQuery;
+    ///
+    /// let query = ReverseDependenciesQuery::builder().normal_only().build();
+    /// let dependents = client
+    ///     .crate_reverse_dependencies_with_query("serde", query)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn crate_reverse_dependencies_with_query(
+        &self,
+        crate_name: &str,
+        query: ReverseDependenciesQuery,
+    ) -> Result<ReverseDependencies, Error> {
+        let mut pages = self.crate_reverse_dependencies_stream(crate_name, query);
+        let dependencies = pages.by_ref().try_collect().await?;
+
+        Ok(ReverseDependencies {
+            dependencies,
+            meta: Meta {
+                total: pages.total().unwrap_or_default(),
+                ..Meta::default()
+            },
+        })
+    }
 
-        Ok(deps)
+    /// Get a stream over all reverse dependencies of a crate matching the
+    /// query.
+    ///
+    /// Pages are fetched lazily, so dependents are available as soon as the
+    /// first page has been received.
+    pub fn crate_reverse_dependencies_stream(
+        &self,
+        crate_name: &str,
+        query: ReverseDependenciesQuery,
+    ) -> Paginated<ReverseDependency> {
+        let client = self.clone();
+        let crate_name = crate_name.to_string();
+        let start = query.start();
+        let per_page = query.per_page;
+        let filter = Arc::new(std::sync::Mutex::new(ReverseDependenciesFilter::new(query)));
+        Paginated::resume(start, move |cursor| {
+            let client = client.clone();
+            let crate_name = crate_name.clone();
+            let filter = filter.clone();
+            async move {
+                let page = match cursor {
+                    PageCursor::Page(page) => page,
+                    _ => 1,
+                };
+                let res = client
+                    .fetch_reverse_dependencies(&crate_name, page, per_page)
+                    .await?;
+                Ok(filter.lock().unwrap().page(page, res))
+            }
+            .boxed()
+        })
     }
 
     /// Get the total count of reverse dependencies for a given crate.
@@ -287,12 +466,65 @@ impl Client {
         Ok(page.meta.total)
     }
 
+    /// Get a single page of versions of a crate.
+    ///
+    /// Use [`PageCursor::First`] for the first page, and the cursor in
+    /// `meta.next_page` for the following ones.
+    pub async fn crate_versions_page(
+        &self,
+        crate_name: &str,
+        cursor: &PageCursor,
+    ) -> Result<VersionsPage, Error> {
+        let url = build_crate_versions_url(&self.base_url, crate_name, cursor)?;
+        self.get(&url, Endpoint::Versions).await
+    }
+
+    /// Load all versions of a crate.
+    pub async fn crate_versions(&self, crate_name: &str) -> Result<Vec<Version>, Error> {
+        self.crate_versions_stream(crate_name).try_collect().await
+    }
+
+    /// Load the audit log of a crate.
+    ///
+    /// The audit actions of all versions, like publishes and yanks, are merged
+    /// into a single chronologically sorted timeline.
+    pub async fn crate_audit_log(&self, crate_name: &str) -> Result<Vec<AuditLogEntry>, Error> {
+        let versions = self.crate_versions(crate_name).await?;
+        Ok(audit_log(&versions))
+    }
+
+    /// Get a stream over all versions of a crate.
+    pub fn crate_versions_stream(&self, crate_name: &str) -> Paginated<Version> {
+        let client = self.clone();
+        let crate_name = crate_name.to_string();
+        Paginated::new(PageCursor::First, move |cursor| {
+            let client = client.clone();
+            let crate_name = crate_name.clone();
+            async move {
+                let res = client.crate_versions_page(&crate_name, &cursor).await?;
+                let next = res
+                    .meta
+                    .next_page
+                    .as_deref()
+                    .and_then(PageCursor::from_query);
+                Ok(Page {
+                    items: res.versions,
+                    total: Some(res.meta.total),
+                    next,
+                })
+            }
+            .boxed()
+        })
+    }
+
     /// Retrieve the authors for a crate version.
     pub async fn crate_authors(&self, crate_name: &str, version: &str) -> Result<Authors, Error> {
         let url = build_crate_authors_url(&self.base_url, crate_name, version)?;
-        self.get::<AuthorsResponse>(&url).await.map(|res| Authors {
-            names: res.meta.names,
-        })
+        self.get::<AuthorsResponse>(&url, Endpoint::Authors)
+            .await
+            .map(|res| Authors {
+                names: res.meta.names,
+            })
     }
 
     /// Retrieve the dependencies of a crate version.
@@ -302,7 +534,7 @@ impl Client {
         version: &str,
     ) -> Result<Vec<Dependency>, Error> {
         let url = build_crate_dependencies_url(&self.base_url, crate_name, version)?;
-        self.get::<Dependencies>(&url)
+        self.get::<Dependencies>(&url, Endpoint::Dependencies)
             .await
             .map(|res| res.dependencies)
     }
@@ -322,33 +554,42 @@ impl Client {
     /// If false, only the data for the latest version will be fetched, if true,
     /// detailed information for all versions will be available.
     /// Note: Each version requires two extra requests.
+    ///
+    /// See [`Client::full_crate_with_options`] for more control.
     pub async fn full_crate(&self, name: &str, all_versions: bool) -> Result<FullCrate, Error> {
-        let krate = self.get_crate(name).await?;
-        let versions = if !all_versions {
-            self.full_version(krate.versions[0].clone())
-                .await
-                .map(|v| vec![v])
-        } else {
-            try_join_all(
-                krate
-                    .versions
-                    .clone()
-                    .into_iter()
-                    .map(|v| self.full_version(v)),
-            )
+        self.full_crate_with_options(name, FullCrateOptions::new().all_versions(all_versions))
             .await
-        }?;
+    }
+
+    /// Retrieve all available information for a crate, like
+    /// [`Client::full_crate`].
+    ///
+    /// Version details are fetched with at most
+    /// [`FullCrateOptions::max_in_flight`] versions at a time.
+    pub async fn full_crate_with_options(
+        &self,
+        name: &str,
+        options: FullCrateOptions,
+    ) -> Result<FullCrate, Error> {
+        let krate = self.get_crate(name).await?;
+        let versions_fut = self
+            .full_versions(&krate.versions, &options)
+            .try_collect::<Vec<_>>()
+            .map_ok(|mut versions| {
+                versions.sort_by_key(|(index, _)| *index);
+                versions.into_iter().map(|(_, v)| v).collect::<Vec<_>>()
+            });
         let dls_fut = self.crate_downloads(name);
         let owners_fut = self.crate_owners(name);
         let reverse_dependencies_fut = self.crate_reverse_dependencies(name);
-        try_join!(dls_fut, owners_fut, reverse_dependencies_fut).map(
-            |(dls, owners, reverse_dependencies)| {
+        try_join!(versions_fut, dls_fut, owners_fut, reverse_dependencies_fut).map(
+            |(versions, dls, owners, reverse_dependencies)| {
                 let data = krate.crate_data;
                 FullCrate {
                     id: data.id,
                     name: data.name,
                     description: data.description,
-                    license: krate.versions[0].license.clone(),
+                    license: krate.versions.first().and_then(|v| v.license.clone()),
                     documentation: data.documentation,
                     homepage: data.homepage,
                     repository: data.repository,
@@ -369,26 +610,159 @@ impl Client {
         )
     }
 
+    /// Get a stream of detailed version information for a crate.
+    ///
+    /// Each [`FullVersion`] is yielded as soon as it has been fetched, so the
+    /// order is not deterministic.
+    ///
+    /// ```rust
+    /// # use futures::prelude::*;
+    /// # use crates_io_api::{AsyncClient, FullCrateOptions};
+    /// # async fn f(client: AsyncClient) -> Result<(), crates_io_api::Error> {
+    /// let options = FullCrateOptions::new().all_versions(true).max_in_flight(4);
+    /// let mut versions = client.full_crate_stream("serde", options);
+    /// while let Some(version) = versions.try_next().await? {
+    ///     println!("{}: {} dependencies", version.num, version.dependencies.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn full_crate_stream(
+        &self,
+        name: &str,
+        options: FullCrateOptions,
+    ) -> BoxStream<'static, Result<FullVersion, Error>> {
+        let client = self.clone();
+        let name = name.to_string();
+        async move {
+            let krate = client.get_crate(&name).await?;
+            let versions = client
+                .full_versions(&krate.versions, &options)
+                .map_ok(|(_, version)| version);
+            Ok::<_, Error>(versions)
+        }
+        .try_flatten_stream()
+        .boxed()
+    }
+
+    /// Fetch details for the requested versions, tagged with their index.
+    fn full_versions(
+        &self,
+        versions: &[Version],
+        options: &FullCrateOptions,
+    ) -> impl Stream<Item = Result<(usize, FullVersion), Error>> + Send + 'static {
+        let count = if options.all_versions {
+            versions.len()
+        } else {
+            versions.len().min(1)
+        };
+        let versions = versions[..count].to_vec();
+        let progress = options.progress.clone();
+        if let Some(progress) = &progress {
+            progress(FullCrateProgress::Started { total: count });
+        }
+
+        let client = self.clone();
+        let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        stream::iter(versions.into_iter().enumerate())
+            .map(move |(index, version)| {
+                let client = client.clone();
+                async move { client.full_version(version).await.map(|v| (index, v)) }
+            })
+            .buffer_unordered(options.max_in_flight.max(1))
+            .inspect_ok(move |(_, version)| {
+                if let Some(progress) = &progress {
+                    let completed = completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    progress(FullCrateProgress::VersionFetched {
+                        version: version.num.clone(),
+                        completed: completed + 1,
+                        total: count,
+                    });
+                }
+            })
+    }
+
     /// Retrieve a page of crates, optionally constrained by a query.
     ///
     /// If you want to get all results without worrying about paging,
-    /// use [`all_crates`].
+    /// use [`Client::crates_stream`].
     pub async fn crates(&self, query: CratesQuery) -> Result<CratesPage, Error> {
         let mut url = self.base_url.join("crates").unwrap();
         query.build(url.query_pairs_mut());
-        self.get(&url).await
+        self.get(&url, Endpoint::Crates).await
     }
 
     /// Get a stream over all crates matching the given [`CratesQuery`].
+    ///
+    /// Follows the seek cursors returned by the API, which stay fast for
+    /// deep listings.
     pub fn crates_stream(&self, filter: CratesQuery) -> CrateStream {
-        CrateStream::new(self.clone(), filter)
+        let position = filter.start();
+        self.crates_stream_resume(CrateStreamCheckpoint {
+            query: filter,
+            position,
+        })
+    }
+
+    /// Continue a stream over crates from a checkpoint obtained with
+    /// [`CrateStream::checkpoint`].
+    pub fn crates_stream_resume(&self, checkpoint: CrateStreamCheckpoint) -> CrateStream {
+        let client = self.clone();
+        let query = checkpoint.query.clone();
+        let inner = Paginated::resume(checkpoint.position, move |cursor| {
+            let client = client.clone();
+            let mut query = query.clone();
+            async move {
+                query.set_cursor(&cursor);
+                let res = client.crates(query).await?;
+                Ok(Page::from_meta(res.crates, &res.meta, &cursor))
+            }
+            .boxed()
+        });
+
+        CrateStream {
+            query: checkpoint.query,
+            inner,
+        }
     }
 
     /// Retrieves a user by username.
     pub async fn user(&self, username: &str) -> Result<User, Error> {
         let url = self.base_url.join(&format!("users/{}", username)).unwrap();
-        self.get::<UserResponse>(&url).await.map(|res| res.user)
+        self.get::<UserResponse>(&url, Endpoint::User)
+            .await
+            .map(|res| res.user)
+    }
+}
+
+/// Turn a raw API response into either the decoded payload or an [`Error`].
+pub(crate) fn parse_response<T: DeserializeOwned>(
+    url: &Url,
+    status: StatusCode,
+    headers: &header::HeaderMap,
+    content: &str,
+) -> Result<T, Error> {
+    if !status.is_success() {
+        return Err(Error::from_response(
+            url,
+            status,
+            headers,
+            content.to_string(),
+        ));
     }
+
+    // Successful responses can still contain an error document.
+    if let Some(errors) = ApiErrors::from_body(content) {
+        return Err(Error::Api {
+            url: url.to_string(),
+            status,
+            errors,
+        });
+    }
+
+    let jd = &mut serde_json::Deserializer::from_str(content);
+    serde_path_to_error::deserialize::<_, T>(jd)
+        .map_err(|err| Error::JsonDecode(JsonDecodeError::new(url, status, content, err)))
 }
 
 pub(crate) fn build_crate_url(base: &Url, crate_name: &str) -> Result<Url, Error> {
@@ -437,12 +811,29 @@ pub(crate) fn build_crate_reverse_deps_url(
     base: &Url,
     crate_name: &str,
     page: u64,
+    per_page: u64,
 ) -> Result<Url, Error> {
     build_crate_url_nested(base, crate_name)?
-        .join(&format!("reverse_dependencies?per_page=100&page={page}"))
+        .join(&format!(
+            "reverse_dependencies?per_page={per_page}&page={page}"
+        ))
         .map_err(Error::from)
 }
 
+pub(crate) fn build_crate_versions_url(
+    base: &Url,
+    crate_name: &str,
+    cursor: &PageCursor,
+) -> Result<Url, Error> {
+    let mut url = build_crate_url_nested(base, crate_name)?.join("versions")?;
+    {
+        let mut q = url.query_pairs_mut();
+        q.append_pair("per_page", "100");
+        cursor.append_to(q);
+    }
+    Ok(url)
+}
+
 pub(crate) fn build_crate_authors_url(
     base: &Url,
     crate_name: &str,
@@ -466,6 +857,7 @@ pub(crate) fn build_crate_dependencies_url(
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::HashSet;
 
     fn build_test_client() -> Client {
         Client::new(
@@ -513,6 +905,27 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_full_crate_stream_async() -> Result<(), Error> {
+        let client = build_test_client();
+        let progress = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = progress.clone();
+        let options = FullCrateOptions::new().on_progress(move |event| {
+            if let FullCrateProgress::VersionFetched { .. } = event {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        });
+
+        let versions = client
+            .full_crate_stream("crates_io_api", options)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(versions.len(), 1);
+        assert_eq!(progress.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_user_get_async() -> Result<(), Error> {
         let client = build_test_client();
@@ -594,6 +1007,16 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_crate_versions_async() -> Result<(), Error> {
+        let client = build_test_client();
+        let krate = client.get_crate("crates_io_api").await?;
+        let versions = client.crate_versions("crates_io_api").await?;
+        assert_eq!(versions.len(), krate.versions.len());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_crate_reverse_dependency_count_async() -> Result<(), Error> {
         let client = build_test_client();
@@ -605,6 +1028,47 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_crate_reverse_dependencies_stream_async() -> Result<(), Error> {
+        let client = build_test_client();
+        let deps = client
+            .crate_reverse_dependencies_stream("serde", ReverseDependenciesQuery::default())
+            .take(150)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(deps.len(), 150);
+        assert!(deps.iter().all(|d| d.dependency.crate_id == "serde"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crate_reverse_dependencies_query_async() -> Result<(), Error> {
+        let client = build_test_client();
+        let query = ReverseDependenciesQuery::builder()
+            .page_size(20)
+            .normal_only()
+            .latest_only()
+            .build();
+        let deps = client
+            .crate_reverse_dependencies_stream("serde", query)
+            .take(50)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(!deps.is_empty());
+        assert!(deps
+            .iter()
+            .all(|d| d.dependency.kind == DependencyKind::Normal && !d.dependency.optional));
+
+        let names = deps
+            .iter()
+            .map(|d| &d.crate_version.crate_name)
+            .collect::<HashSet<_>>();
+        assert_eq!(names.len(), deps.len());
+
+        Ok(())
+    }
+
     /// Regression test for https://github.com/theduke/crates-io-api/issues/44
     #[tokio::test]
     async fn test_get_crate_with_slash() {
@@ -0,0 +1,556 @@
+//! Response caching with HTTP revalidation.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{header, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_derive::*;
+
+use crate::{async_client::parse_response, Error};
+
+/// A cached API response body together with its validators.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedResponse {
+    /// The raw response body.
+    pub body: String,
+    /// Value of the `ETag` response header.
+    pub etag: Option<String>,
+    /// Value of the `Last-Modified` response header.
+    pub last_modified: Option<String>,
+    /// When the response was stored or last revalidated.
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    /// Time elapsed since the response was stored or last revalidated.
+    pub fn age(&self) -> Duration {
+        self.stored_at.elapsed().unwrap_or_default()
+    }
+}
+
+/// Storage backend for cached responses.
+///
+/// Implementations must be safe to share between threads, since a single
+/// cache can be used by multiple clients.
+/// Storage failures should be treated as cache misses.
+pub trait CacheStorage: Send + Sync {
+    /// Look up the response stored for the given key.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store a response under the given key.
+    fn put(&self, key: &str, response: CachedResponse);
+}
+
+/// In-memory cache storage that evicts the least recently used entries.
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+    tick: u64,
+    entries: HashMap<String, (CachedResponse, u64)>,
+    order: BTreeMap<u64, String>,
+}
+
+impl LruState {
+    fn touch(&mut self, key: &str) -> Option<&CachedResponse> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (entry, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        self.order.insert(tick, key.to_string());
+        *last_used = tick;
+        Some(entry)
+    }
+}
+
+impl MemoryCache {
+    /// Create a new cache holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(LruState::default()),
+        }
+    }
+}
+
+impl CacheStorage for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut state = self.state.lock().unwrap();
+        state.touch(key).cloned()
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some((entry, _)) = state.entries.get_mut(key) {
+            *entry = response;
+            state.touch(key);
+            return;
+        }
+
+        while state.entries.len() >= self.capacity {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.order.insert(tick, key.to_string());
+        state.entries.insert(key.to_string(), (response, tick));
+    }
+}
+
+/// Cache storage that keeps one JSON file per response in a directory.
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    response: CachedResponse,
+}
+
+impl DiskCache {
+    /// Use the given directory for storage, creating it if necessary.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // FNV-1a, which is stable across Rust versions and platforms.
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        self.dir.join(format!("{hash:016x}.json"))
+    }
+}
+
+impl CacheStorage for DiskCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let data = std::fs::read(self.path(key)).ok()?;
+        let entry: DiskEntry = serde_json::from_slice(&data).ok()?;
+        // Guard against hash collisions.
+        if entry.key == key {
+            Some(entry.response)
+        } else {
+            None
+        }
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        let entry = DiskEntry {
+            key: key.to_string(),
+            response,
+        };
+        let Ok(data) = serde_json::to_vec(&entry) else {
+            return;
+        };
+
+        // Write to a temporary file first so readers never see partial data.
+        // The name is unique per write, so concurrent writers of the same key
+        // in this and other processes do not clobber each other's file.
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let path = self.path(key);
+        let tmp = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        if std::fs::write(&tmp, data).is_ok() && std::fs::rename(&tmp, &path).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+}
+
+/// Classes of API endpoints, used to configure cache freshness per endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Endpoint {
+    /// crates.io wide summary.
+    Summary,
+    /// Crate details, including the version list.
+    Crate,
+    /// Version listing of a crate.
+    Versions,
+    /// Download stats of a crate.
+    CrateDownloads,
+    /// Owners of a crate.
+    CrateOwners,
+    /// Reverse dependencies of a crate.
+    ReverseDependencies,
+    /// Authors of a crate version.
+    Authors,
+    /// Dependencies of a crate version.
+    Dependencies,
+    /// Crate listings.
+    Crates,
+    /// User details.
+    User,
+}
+
+impl Endpoint {
+    /// Returns `true` for endpoints serving data of a published version,
+    /// which never changes.
+    ///
+    /// Cached responses of these endpoints are always fresh, unless a
+    /// [`ResponseCache::ttl`] is set for the endpoint.
+    pub fn is_immutable(self) -> bool {
+        matches!(self, Self::Authors | Self::Dependencies)
+    }
+}
+
+/// Response cache configuration for a client.
+///
+/// Cached responses are revalidated with the server using conditional
+/// requests (`If-None-Match` / `If-Modified-Since`).
+/// A `304 Not Modified` response is served from the cache.
+///
+/// Responses can also be served without contacting the server while they
+/// are fresh, either for all endpoints ([`ResponseCache::max_age`]) or per
+/// [`Endpoint`] ([`ResponseCache::ttl`]). Responses of
+/// [immutable](Endpoint::is_immutable) endpoints are fresh forever by
+/// default.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{Endpoint, ResponseCache};
+/// use std::time::Duration;
+///
+/// let cache = ResponseCache::disk("/tmp/crates-io-cache")?
+///     .ttl(Endpoint::Summary, Duration::from_secs(10 * 60))
+///     .ttl(Endpoint::CrateDownloads, Duration::from_secs(24 * 60 * 60));
+/// # std::mem::drop(cache);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = crates_io_api::SyncClient::new(
+///     "my_bot (help@my_bot.com)",
+///     std::time::Duration::from_millis(1000),
+/// )?
+/// .with_cache(crates_io_api::ResponseCache::memory(1000));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ResponseCache {
+    storage: Arc<dyn CacheStorage>,
+    max_age: Option<Duration>,
+    ttls: HashMap<Endpoint, Duration>,
+    offline: bool,
+}
+
+impl ResponseCache {
+    /// Create a cache backed by the given storage.
+    pub fn new(storage: impl CacheStorage + 'static) -> Self {
+        Self::with_storage(Arc::new(storage))
+    }
+
+    /// Create a cache backed by shared storage.
+    pub fn with_storage(storage: Arc<dyn CacheStorage>) -> Self {
+        Self {
+            storage,
+            max_age: None,
+            ttls: HashMap::new(),
+            offline: false,
+        }
+    }
+
+    /// Create an in-memory cache holding at most `capacity` responses.
+    pub fn memory(capacity: usize) -> Self {
+        Self::new(MemoryCache::new(capacity))
+    }
+
+    /// Create a cache that stores responses in the given directory.
+    pub fn disk(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        DiskCache::new(dir).map(Self::new)
+    }
+
+    /// Serve cached responses younger than `max_age` without contacting the
+    /// server.
+    ///
+    /// Useful for offline runs. Older responses are still revalidated.
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Serve cached responses of the given endpoint class younger than `ttl`
+    /// without contacting the server.
+    ///
+    /// Overrides [`ResponseCache::max_age`] and the default for
+    /// [immutable](Endpoint::is_immutable) endpoints.
+    /// Use [`Duration::MAX`] to never revalidate a response.
+    #[must_use]
+    pub fn ttl(mut self, endpoint: Endpoint, ttl: Duration) -> Self {
+        self.ttls.insert(endpoint, ttl);
+        self
+    }
+
+    /// Only serve responses from the cache, regardless of their age.
+    ///
+    /// Requests for uncached resources fail with [`Error::CacheMiss`](crate::Error::CacheMiss).
+    #[must_use]
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Look up the cached response for a request.
+    ///
+    /// Fresh responses are parsed and returned without contacting the server.
+    /// In offline mode, uncached requests fail with [`Error::CacheMiss`].
+    pub(crate) fn lookup<T: DeserializeOwned>(
+        &self,
+        url: &Url,
+        endpoint: Endpoint,
+    ) -> Result<Lookup<T>, Error> {
+        match self.storage.get(url.as_str()) {
+            Some(entry) if self.is_fresh(endpoint, &entry) => {
+                parse_response(url, StatusCode::OK, &header::HeaderMap::new(), &entry.body)
+                    .map(Lookup::Fresh)
+            }
+            Some(entry) => Ok(Lookup::Send(Some(entry))),
+            None if self.offline => Err(Error::CacheMiss {
+                url: url.to_string(),
+            }),
+            None => Ok(Lookup::Send(None)),
+        }
+    }
+
+    /// Parse the server response to a request, serving the revalidated entry
+    /// for a `304 Not Modified` response and storing successful responses.
+    pub(crate) fn response<T: DeserializeOwned>(
+        &self,
+        url: &Url,
+        cached: Option<CachedResponse>,
+        status: StatusCode,
+        headers: &header::HeaderMap,
+        content: String,
+    ) -> Result<T, Error> {
+        match cached {
+            Some(entry) if status == StatusCode::NOT_MODIFIED => {
+                let entry = self.revalidated(url, entry);
+                parse_response(url, StatusCode::OK, headers, &entry.body)
+            }
+            _ => {
+                let value = parse_response(url, status, headers, &content)?;
+                self.store(url, headers, content);
+                Ok(value)
+            }
+        }
+    }
+
+    fn is_fresh(&self, endpoint: Endpoint, entry: &CachedResponse) -> bool {
+        if self.offline {
+            return true;
+        }
+        match self.ttls.get(&endpoint) {
+            Some(ttl) => entry.age() <= *ttl,
+            None if endpoint.is_immutable() => true,
+            None => self.max_age.is_some_and(|ttl| entry.age() <= ttl),
+        }
+    }
+
+    /// Headers for a conditional request revalidating the given entry.
+    pub(crate) fn conditional_headers(entry: &CachedResponse) -> header::HeaderMap {
+        let mut headers = header::HeaderMap::new();
+        if let Some(value) = entry
+            .etag
+            .as_deref()
+            .and_then(|v| header::HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_NONE_MATCH, value);
+        }
+        if let Some(value) = entry
+            .last_modified
+            .as_deref()
+            .and_then(|v| header::HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_MODIFIED_SINCE, value);
+        }
+        headers
+    }
+
+    /// Mark an entry as confirmed unchanged by the server.
+    fn revalidated(&self, url: &Url, mut entry: CachedResponse) -> CachedResponse {
+        entry.stored_at = SystemTime::now();
+        self.storage.put(url.as_str(), entry.clone());
+        entry
+    }
+
+    fn store(&self, url: &Url, headers: &header::HeaderMap, body: String) {
+        let value = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let entry = CachedResponse {
+            body,
+            etag: value(header::ETAG),
+            last_modified: value(header::LAST_MODIFIED),
+            stored_at: SystemTime::now(),
+        };
+        self.storage.put(url.as_str(), entry);
+    }
+}
+
+/// Result of looking up a request in a [`ResponseCache`].
+pub(crate) enum Lookup<T> {
+    /// A fresh cached response, served without contacting the server.
+    Fresh(T),
+    /// The request has to be sent, revalidating the cached entry if any.
+    Send(Option<CachedResponse>),
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("max_age", &self.max_age)
+            .field("ttls", &self.ttls)
+            .field("offline", &self.offline)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn response(body: &str) -> CachedResponse {
+        CachedResponse {
+            body: body.to_string(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            stored_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.put("a", response("a"));
+        cache.put("b", response("b"));
+        assert!(cache.get("a").is_some());
+        cache.put("c", response("c"));
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_response_cache_freshness() {
+        let mut entry = response("{}");
+        entry.stored_at = SystemTime::now() - Duration::from_secs(60 * 60);
+
+        let cache = ResponseCache::memory(1)
+            .max_age(Duration::from_secs(2 * 60 * 60))
+            .ttl(Endpoint::Summary, Duration::from_secs(10 * 60));
+        assert!(!cache.is_fresh(Endpoint::Summary, &entry));
+        assert!(cache.is_fresh(Endpoint::Crate, &entry));
+
+        // Immutable endpoints are fresh without any configuration, unless
+        // overridden.
+        let cache = ResponseCache::memory(1);
+        assert!(!cache.is_fresh(Endpoint::Crate, &entry));
+        assert!(cache.is_fresh(Endpoint::Dependencies, &entry));
+        assert!(cache.is_fresh(Endpoint::Authors, &entry));
+        let cache = ResponseCache::memory(1).ttl(Endpoint::Dependencies, Duration::from_secs(60));
+        assert!(!cache.is_fresh(Endpoint::Dependencies, &entry));
+
+        let cache = ResponseCache::memory(1).offline(true);
+        assert!(cache.is_fresh(Endpoint::Summary, &entry));
+    }
+
+    #[test]
+    fn test_response_cache_lookup_and_revalidation() {
+        let url = Url::parse("https://crates.io/api/v1/summary").unwrap();
+        let lookup =
+            |cache: &ResponseCache, endpoint| cache.lookup::<serde_json::Value>(&url, endpoint);
+
+        let cache = ResponseCache::memory(1).offline(true);
+        assert!(matches!(
+            lookup(&cache, Endpoint::Summary),
+            Err(Error::CacheMiss { .. })
+        ));
+
+        let cache = ResponseCache::memory(1);
+        assert!(matches!(
+            lookup(&cache, Endpoint::Summary),
+            Ok(Lookup::Send(None))
+        ));
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::ETAG, header::HeaderValue::from_static("\"abc\""));
+        let value: serde_json::Value = cache
+            .response(
+                &url,
+                None,
+                StatusCode::OK,
+                &headers,
+                r#"{"a":1}"#.to_string(),
+            )
+            .unwrap();
+        assert_eq!(value["a"], 1);
+
+        let Ok(Lookup::Send(Some(entry))) = lookup(&cache, Endpoint::Summary) else {
+            panic!("expected a stale entry");
+        };
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        let value: serde_json::Value = cache
+            .response(
+                &url,
+                Some(entry),
+                StatusCode::NOT_MODIFIED,
+                &header::HeaderMap::new(),
+                String::new(),
+            )
+            .unwrap();
+        assert_eq!(value["a"], 1);
+
+        let cache = cache.ttl(Endpoint::Summary, Duration::from_secs(60));
+        assert!(matches!(
+            lookup(&cache, Endpoint::Summary),
+            Ok(Lookup::Fresh(value)) if value["a"] == 1
+        ));
+    }
+
+    #[test]
+    fn test_disk_cache_roundtrip() {
+        let dir =
+            std::env::temp_dir().join(format!("crates_io_api_disk_cache_{}", std::process::id()));
+        let cache = DiskCache::new(&dir).unwrap();
+        cache.put("https://crates.io/api/v1/summary", response("{}"));
+
+        let entry = cache.get("https://crates.io/api/v1/summary").unwrap();
+        assert_eq!(entry.body, "{}");
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert!(cache.get("https://crates.io/api/v1/crates").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
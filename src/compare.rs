@@ -0,0 +1,241 @@
+//! Side-by-side comparison of crates.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde_derive::*;
+
+use crate::types::{CrateResponse, Dependency, DependencyKind, User};
+
+/// The metrics of a single crate in a [`CrateComparison`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ComparedCrate {
+    /// The name of the crate.
+    pub name: String,
+    /// The latest stable version, or the latest version if there is no
+    /// stable one.
+    pub version: String,
+    /// All-time downloads.
+    pub downloads: u64,
+    /// Downloads in the last 90 days.
+    pub recent_downloads: Option<u64>,
+    /// The number of non-yanked versions.
+    pub releases: usize,
+    /// The average number of days between non-yanked releases.
+    pub average_release_interval_days: Option<f64>,
+    /// When the latest non-yanked version was published.
+    pub last_release: Option<DateTime<Utc>>,
+    /// Days since the latest non-yanked version was published.
+    pub last_release_age_days: Option<i64>,
+    /// The number of owners, including teams.
+    pub owners: usize,
+    /// The number of normal and build dependencies of `version`.
+    pub dependencies: usize,
+    /// The license of `version`.
+    pub license: Option<String>,
+    /// The minimum supported Rust version of `version`.
+    pub rust_version: Option<String>,
+    /// The number of crates depending on the crate.
+    pub reverse_dependencies: u64,
+}
+
+impl ComparedCrate {
+    pub(crate) fn new(
+        krate: &CrateResponse,
+        owners: &[User],
+        dependencies: &[Dependency],
+        reverse_dependencies: u64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let data = &krate.crate_data;
+        let num = compared_version(krate);
+        let version = krate.versions.iter().find(|v| v.num == num);
+
+        let mut published = krate
+            .versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .map(|v| v.created_at)
+            .collect::<Vec<_>>();
+        published.sort();
+        let average_release_interval_days = match (published.first(), published.last()) {
+            (Some(first), Some(last)) if published.len() > 1 => {
+                let span = (*last - *first).num_seconds() as f64 / 86_400.0;
+                Some(span / (published.len() - 1) as f64)
+            }
+            _ => None,
+        };
+        let last_release = published.last().copied();
+
+        ComparedCrate {
+            name: data.name.clone(),
+            version: num.to_string(),
+            downloads: data.downloads,
+            recent_downloads: data.recent_downloads,
+            releases: published.len(),
+            average_release_interval_days,
+            last_release,
+            last_release_age_days: last_release.map(|date| (now - date).num_days()),
+            owners: owners.len(),
+            dependencies: dependencies
+                .iter()
+                .filter(|dep| dep.kind != DependencyKind::Dev)
+                .count(),
+            license: version.and_then(|v| v.license.clone()),
+            rust_version: version.and_then(|v| v.rust_version.clone()),
+            reverse_dependencies,
+        }
+    }
+}
+
+/// The result of `compare_crates` on the [`SyncClient`](crate::SyncClient)
+/// or [`AsyncClient`](crate::AsyncClient).
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::SyncClient;
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let comparison = client.compare_crates(&["reqwest", "ureq"])?;
+/// println!("{}", serde_json::to_string_pretty(&comparison)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrateComparison {
+    /// The compared crates, in the requested order.
+    pub crates: Vec<ComparedCrate>,
+}
+
+impl CrateComparison {
+    /// The metrics of a crate by name.
+    pub fn get(&self, name: &str) -> Option<&ComparedCrate> {
+        self.crates.iter().find(|c| c.name == name)
+    }
+}
+
+/// The version whose dependencies, license and MSRV are compared.
+pub(crate) fn compared_version(krate: &CrateResponse) -> &str {
+    krate
+        .crate_data
+        .max_stable_version
+        .as_deref()
+        .unwrap_or(&krate.crate_data.max_version)
+}
+
+/// The current time, for the age of the last release.
+pub(crate) fn now() -> DateTime<Utc> {
+    let elapsed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Utc.timestamp_opt(elapsed.as_secs() as i64, 0)
+        .single()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(num: &str, created_at: &str, yanked: bool) -> serde_json::Value {
+        serde_json::json!({
+            "crate": "demo",
+            "created_at": created_at,
+            "updated_at": created_at,
+            "dl_path": "",
+            "downloads": 0,
+            "features": {},
+            "id": 1,
+            "num": num,
+            "yanked": yanked,
+            "license": "MIT OR Apache-2.0",
+            "readme_path": null,
+            "links": {"dependencies": "", "version_downloads": ""},
+            "crate_size": null,
+            "published_by": null,
+            "rust_version": "1.63",
+            "checksum": "",
+        })
+    }
+
+    fn dependency(name: &str, kind: &str) -> Dependency {
+        serde_json::from_value(serde_json::json!({
+            "crate_id": name,
+            "default_features": true,
+            "downloads": 0,
+            "features": [],
+            "id": 1,
+            "kind": kind,
+            "optional": false,
+            "req": "^1",
+            "target": null,
+            "version_id": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compared_crate() {
+        let mut krate = serde_json::json!({
+            "categories": [],
+            "crate": {
+                "id": "demo",
+                "name": "demo",
+                "description": null,
+                "license": null,
+                "documentation": null,
+                "homepage": null,
+                "repository": null,
+                "downloads": 1000,
+                "recent_downloads": 100,
+                "categories": null,
+                "keywords": null,
+                "versions": null,
+                "max_version": "2.0.0-rc.1",
+                "max_stable_version": "1.1.0",
+                "links": {
+                    "owner_team": "",
+                    "owner_user": "",
+                    "owners": "",
+                    "reverse_dependencies": "",
+                    "version_downloads": "",
+                    "versions": null,
+                },
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z",
+                "exact_match": null,
+            },
+            "keywords": [],
+            "versions": [
+                version("2.0.0-rc.1", "2024-03-01T00:00:00Z", false),
+                version("1.2.0", "2024-02-15T00:00:00Z", true),
+                version("1.1.0", "2024-01-21T00:00:00Z", false),
+                version("1.0.0", "2024-01-01T00:00:00Z", false),
+            ],
+        });
+        krate["versions"][2]["rust_version"] = "1.70".into();
+        let krate = serde_json::from_value(krate).unwrap();
+        let dependencies = vec![
+            dependency("serde", "normal"),
+            dependency("cc", "build"),
+            dependency("criterion", "dev"),
+        ];
+        let now = Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap();
+
+        let compared = ComparedCrate::new(&krate, &[], &dependencies, 7, now);
+        assert_eq!(compared.version, "1.1.0");
+        assert_eq!(compared.downloads, 1000);
+        assert_eq!(compared.releases, 3);
+        assert_eq!(compared.average_release_interval_days, Some(30.0));
+        assert_eq!(compared.last_release_age_days, Some(10));
+        assert_eq!(compared.dependencies, 2);
+        assert_eq!(compared.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(compared.rust_version.as_deref(), Some("1.70"));
+        assert_eq!(compared.reverse_dependencies, 7);
+
+        let comparison = CrateComparison {
+            crates: vec![compared],
+        };
+        let json = serde_json::to_value(&comparison).unwrap();
+        assert_eq!(json["crates"][0]["owners"], 0);
+        assert_eq!(comparison.get("demo").unwrap().owners, 0);
+    }
+}
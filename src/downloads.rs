@@ -0,0 +1,718 @@
+//! Aggregation of crate download statistics.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use serde_derive::*;
+
+use crate::types::{CrateDownloads, FullCrate, Version};
+
+/// Downloads in a single period of a [`DownloadSeries`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadPoint {
+    /// The first day of the period.
+    pub date: NaiveDate,
+    /// The downloads in the period.
+    pub downloads: u64,
+}
+
+/// A derived value in a single period of a [`DownloadSeries`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SeriesValue {
+    /// The first day of the period.
+    pub date: NaiveDate,
+    /// The value for the period.
+    pub value: f64,
+}
+
+/// Downloads per day, week or month, in chronological order.
+///
+/// Periods without downloads are included with zero downloads. The first and
+/// last period of weekly and monthly series may only be partially covered by
+/// the download data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct DownloadSeries {
+    /// The downloads per period.
+    pub points: Vec<DownloadPoint>,
+}
+
+impl DownloadSeries {
+    /// The downloads of all periods.
+    pub fn total(&self) -> u64 {
+        self.points.iter().map(|p| p.downloads).sum()
+    }
+
+    /// The trailing average over `window` periods.
+    ///
+    /// The first value is for the period that completes the first window.
+    pub fn moving_average(&self, window: usize) -> Vec<SeriesValue> {
+        if window == 0 {
+            return Vec::new();
+        }
+        self.points
+            .windows(window)
+            .map(|points| SeriesValue {
+                date: points[window - 1].date,
+                value: points.iter().map(|p| p.downloads as f64).sum::<f64>() / window as f64,
+            })
+            .collect()
+    }
+
+    /// The relative change of downloads compared to the previous period,
+    /// where `0.5` means 50% more downloads.
+    ///
+    /// Periods following a period without downloads are skipped.
+    pub fn growth_rates(&self) -> Vec<SeriesValue> {
+        self.points
+            .windows(2)
+            .filter(|pair| pair[0].downloads > 0)
+            .map(|pair| SeriesValue {
+                date: pair[1].date,
+                value: (pair[1].downloads as f64 - pair[0].downloads as f64)
+                    / pair[0].downloads as f64,
+            })
+            .collect()
+    }
+
+    /// Sum up the points by the period returned by `period`.
+    fn group_by(&self, period: impl Fn(NaiveDate) -> NaiveDate) -> Self {
+        let mut grouped = BTreeMap::new();
+        for point in &self.points {
+            *grouped.entry(period(point.date)).or_insert(0) += point.downloads;
+        }
+        Self {
+            points: grouped
+                .into_iter()
+                .map(|(date, downloads)| DownloadPoint { date, downloads })
+                .collect(),
+        }
+    }
+}
+
+/// The downloads of a single version.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionDownloadTotal {
+    /// The id of the version.
+    pub version_id: u64,
+    /// The version number, if the version is known.
+    pub num: Option<String>,
+    /// The downloads of the version.
+    pub downloads: u64,
+}
+
+/// The downloads of all versions of a semver-compatible release series.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MajorDownloadTotal {
+    /// The series, like `1` for `1.x.y` or `0.3` for `0.3.x`.
+    pub major: String,
+    /// The downloads of the series.
+    pub downloads: u64,
+}
+
+/// How fast a release was adopted, see [`DownloadAnalytics::adoption`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseAdoption {
+    /// The id of the release.
+    pub version_id: u64,
+    /// The version number of the release.
+    pub num: String,
+    /// The day the release was published.
+    pub published: NaiveDate,
+    /// The version number of the previous release, see
+    /// [`DownloadAnalytics::adoption`].
+    pub previous: Option<String>,
+    /// The first day the release had more downloads than the previous
+    /// release.
+    pub overtaken_on: Option<NaiveDate>,
+    /// Days from publication until the release overtook the previous release.
+    pub days_to_overtake: Option<i64>,
+}
+
+/// A release series that users do not migrate away from, see
+/// [`DownloadAnalytics::stuck_majors`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StuckMajor {
+    /// The old series, like `1` or `0.3`.
+    pub major: String,
+    /// The series that superseded it.
+    pub superseded_by: String,
+    /// The day the first release of `superseded_by` was published.
+    pub superseded_on: NaiveDate,
+    /// The share of recent downloads still on the old series.
+    pub share: f64,
+}
+
+#[derive(Debug, Clone)]
+struct KnownVersion {
+    num: String,
+    created_at: DateTime<Utc>,
+    /// The parsed version number of stable, non-yanked versions.
+    release: Option<(u64, u64, u64)>,
+}
+
+/// Aggregations over the [`CrateDownloads`] of a crate.
+///
+/// Version ids are joined with the versions of the crate to report version
+/// numbers. Downloads that are not attributed to a version, like old
+/// `extra_downloads`, only count towards the time series.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{DownloadAnalytics, SyncClient};
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let downloads = client.crate_downloads("crates_io_api")?;
+/// let versions = client.crate_versions("crates_io_api")?;
+/// let analytics = DownloadAnalytics::new(&downloads, &versions);
+/// for point in analytics.weekly().moving_average(4) {
+///     println!("{}: {:.0}", point.date, point.value);
+/// }
+/// println!("{:?} on the latest version", analytics.latest_share());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DownloadAnalytics {
+    /// `(date, version id, downloads)` rows.
+    rows: Vec<(NaiveDate, Option<u64>, u64)>,
+    versions: HashMap<u64, KnownVersion>,
+}
+
+impl DownloadAnalytics {
+    /// Analyze the downloads of a crate with the given versions.
+    pub fn new(downloads: &CrateDownloads, versions: &[Version]) -> Self {
+        Self::from_parts(
+            downloads,
+            versions
+                .iter()
+                .map(|v| (v.id, &v.num, v.created_at, v.yanked)),
+        )
+    }
+
+    fn from_parts<'a>(
+        downloads: &CrateDownloads,
+        versions: impl Iterator<Item = (u64, &'a String, DateTime<Utc>, bool)>,
+    ) -> Self {
+        let rows = downloads
+            .version_downloads
+            .iter()
+            .map(|d| (d.date, Some(d.version), d.downloads))
+            .chain(
+                downloads
+                    .meta
+                    .extra_downloads
+                    .iter()
+                    .map(|d| (d.date, None, d.downloads)),
+            )
+            .collect();
+        let versions = versions
+            .map(|(id, num, created_at, yanked)| {
+                let version = KnownVersion {
+                    num: num.clone(),
+                    created_at,
+                    release: release_number(num).filter(|_| !yanked),
+                };
+                (id, version)
+            })
+            .collect();
+        Self { rows, versions }
+    }
+
+    /// Total downloads per day.
+    pub fn daily(&self) -> DownloadSeries {
+        let mut days = BTreeMap::new();
+        for (date, _, downloads) in &self.rows {
+            *days.entry(*date).or_insert(0) += downloads;
+        }
+
+        let mut points = Vec::new();
+        let mut days = days.into_iter().peekable();
+        while let Some((date, downloads)) = days.next() {
+            points.push(DownloadPoint { date, downloads });
+            let next = days.peek().map(|(next, _)| *next);
+            let mut gap = date.succ_opt();
+            while let Some(day) = gap.filter(|day| Some(*day) < next) {
+                points.push(DownloadPoint {
+                    date: day,
+                    downloads: 0,
+                });
+                gap = day.succ_opt();
+            }
+        }
+        DownloadSeries { points }
+    }
+
+    /// Total downloads per ISO week, starting on Monday.
+    pub fn weekly(&self) -> DownloadSeries {
+        self.daily().group_by(|date| {
+            let week = date.iso_week();
+            NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon).unwrap_or(date)
+        })
+    }
+
+    /// Total downloads per calendar month.
+    pub fn monthly(&self) -> DownloadSeries {
+        self.daily()
+            .group_by(|date| date.with_day(1).unwrap_or(date))
+    }
+
+    /// Downloads per version, most downloaded first.
+    pub fn by_version(&self) -> Vec<VersionDownloadTotal> {
+        let mut totals = HashMap::new();
+        for (_, version, downloads) in &self.rows {
+            if let Some(version) = version {
+                *totals.entry(*version).or_insert(0) += downloads;
+            }
+        }
+
+        let mut totals = totals
+            .into_iter()
+            .map(|(version_id, downloads)| VersionDownloadTotal {
+                version_id,
+                num: self.versions.get(&version_id).map(|v| v.num.clone()),
+                downloads,
+            })
+            .collect::<Vec<_>>();
+        totals.sort_by(|a, b| {
+            b.downloads
+                .cmp(&a.downloads)
+                .then(a.version_id.cmp(&b.version_id))
+        });
+        totals
+    }
+
+    /// Downloads per semver-compatible release series, most downloaded
+    /// first.
+    ///
+    /// Downloads of unknown versions are not included.
+    pub fn by_major(&self) -> Vec<MajorDownloadTotal> {
+        let mut totals = HashMap::<String, u64>::new();
+        for total in self.by_version() {
+            if let Some(major) = total.num.as_deref().and_then(major_series) {
+                *totals.entry(major).or_insert(0) += total.downloads;
+            }
+        }
+
+        let mut totals = totals
+            .into_iter()
+            .map(|(major, downloads)| MajorDownloadTotal { major, downloads })
+            .collect::<Vec<_>>();
+        totals.sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.major.cmp(&b.major)));
+        totals
+    }
+
+    /// The highest stable, non-yanked version.
+    ///
+    /// Backports to older series that are published later do not count as
+    /// the latest version.
+    pub fn latest_version(&self) -> Option<(u64, &str)> {
+        self.releases().last().map(|(id, v)| (*id, v.num.as_str()))
+    }
+
+    /// The share of the downloads attributed to a version that went to the
+    /// [latest version](Self::latest_version), between `0.0` and `1.0`.
+    ///
+    /// Returns `None` if there is no latest version or no downloads.
+    pub fn latest_share(&self) -> Option<f64> {
+        let (latest, _) = self.latest_version()?;
+        let (mut on_latest, mut total) = (0, 0);
+        for (_, version, downloads) in &self.rows {
+            if let Some(version) = version {
+                total += downloads;
+                if *version == latest {
+                    on_latest += downloads;
+                }
+            }
+        }
+        if total == 0 {
+            None
+        } else {
+            Some(on_latest as f64 / total as f64)
+        }
+    }
+
+    /// How fast each release overtook the release before it.
+    ///
+    /// Releases are the stable, non-yanked versions in version order. The
+    /// predecessor of a release is the highest lower release published
+    /// before it, so a backport like `0.9.2` published after `1.1.0` is
+    /// compared with `0.9.1`. A release overtakes its predecessor on the
+    /// first day it has more downloads. Only days covered by the download
+    /// data are considered, so releases published before the first day of
+    /// the data are not measured.
+    pub fn adoption(&self) -> Vec<ReleaseAdoption> {
+        let first_day = self.rows.iter().map(|(date, _, _)| *date).min();
+        let days = self.daily();
+        let releases = self.releases();
+
+        let mut adoption = Vec::new();
+        for (index, (id, version)) in releases.iter().enumerate() {
+            let published = version.created_at.naive_utc().date();
+            let previous = releases[..index]
+                .iter()
+                .rev()
+                .find(|(_, other)| other.created_at < version.created_at)
+                .copied();
+            let overtaken_on = previous
+                .filter(|_| first_day.is_some_and(|first| first <= published))
+                .and_then(|(previous, _)| {
+                    let ours = self.version_daily(*id);
+                    let theirs = self.version_daily(previous);
+                    days.points
+                        .iter()
+                        .map(|p| p.date)
+                        .filter(|date| *date >= published)
+                        .find(|date| {
+                            ours.get(date).copied().unwrap_or(0)
+                                > theirs.get(date).copied().unwrap_or(0)
+                        })
+                });
+
+            adoption.push(ReleaseAdoption {
+                version_id: *id,
+                num: version.num.clone(),
+                published,
+                previous: previous.map(|(_, v)| v.num.clone()),
+                overtaken_on,
+                days_to_overtake: overtaken_on.map(|date| (date - published).num_days()),
+            });
+        }
+        adoption
+    }
+
+    /// Release series that still receive more than `max_share` of the
+    /// downloads, `months` after a newer series was first released.
+    ///
+    /// The share is computed over the last seven days of the download data,
+    /// which also serve as the reference date. `max_share` is between `0.0`
+    /// and `1.0`.
+    pub fn stuck_majors(&self, max_share: f64, months: u32) -> Vec<StuckMajor> {
+        let Some(last_day) = self.rows.iter().map(|(date, _, _)| *date).max() else {
+            return Vec::new();
+        };
+        let window_start = last_day - chrono::Duration::days(6);
+
+        let mut recent = HashMap::<String, u64>::new();
+        let mut total = 0;
+        for (date, version, downloads) in &self.rows {
+            let Some(version) = version.filter(|_| *date >= window_start) else {
+                continue;
+            };
+            total += downloads;
+            let major = self
+                .versions
+                .get(&version)
+                .and_then(|v| major_series(&v.num));
+            if let Some(major) = major {
+                *recent.entry(major).or_insert(0) += downloads;
+            }
+        }
+        if total == 0 {
+            return Vec::new();
+        }
+
+        let mut first_releases = HashMap::<String, NaiveDate>::new();
+        for (_, version) in self.releases() {
+            if let Some(major) = major_series(&version.num) {
+                let published = version.created_at.naive_utc().date();
+                let first = first_releases.entry(major).or_insert(published);
+                *first = (*first).min(published);
+            }
+        }
+        let mut series = first_releases.into_iter().collect::<Vec<_>>();
+        series.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+
+        series
+            .windows(2)
+            .filter(|pair| add_months(pair[1].1, months).is_some_and(|date| date <= last_day))
+            .filter_map(|pair| {
+                let share = recent.get(&pair[0].0).copied().unwrap_or(0) as f64 / total as f64;
+                (share > max_share).then(|| StuckMajor {
+                    major: pair[0].0.clone(),
+                    superseded_by: pair[1].0.clone(),
+                    superseded_on: pair[1].1,
+                    share,
+                })
+            })
+            .collect()
+    }
+
+    /// Stable, non-yanked versions in version order.
+    fn releases(&self) -> Vec<(u64, &KnownVersion)> {
+        let mut releases = self
+            .versions
+            .iter()
+            .filter(|(_, v)| v.release.is_some())
+            .map(|(id, v)| (*id, v))
+            .collect::<Vec<_>>();
+        releases.sort_by(|a, b| {
+            a.1.release
+                .cmp(&b.1.release)
+                .then(a.1.created_at.cmp(&b.1.created_at))
+                .then(a.0.cmp(&b.0))
+        });
+        releases
+    }
+
+    /// Downloads of a single version per day.
+    fn version_daily(&self, version_id: u64) -> HashMap<NaiveDate, u64> {
+        let mut days = HashMap::new();
+        for (date, version, downloads) in &self.rows {
+            if *version == Some(version_id) {
+                *days.entry(*date).or_insert(0) += downloads;
+            }
+        }
+        days
+    }
+}
+
+impl FullCrate {
+    /// Aggregations over the downloads of the crate.
+    pub fn download_analytics(&self) -> DownloadAnalytics {
+        DownloadAnalytics::from_parts(
+            &self.downloads,
+            self.versions
+                .iter()
+                .map(|v| (v.id, &v.num, v.created_at, v.yanked)),
+        )
+    }
+}
+
+/// Add calendar months to a date, clamping the day to the end of the month.
+fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    let month0 = date.month0().checked_add(months)?;
+    let year = date.year().checked_add((month0 / 12) as i32)?;
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month0 % 12 + 1, day))
+}
+
+/// The `(major, minor, patch)` of a stable version number, ignoring build
+/// metadata.
+fn release_number(num: &str) -> Option<(u64, u64, u64)> {
+    let num = num.split('+').next()?;
+    let mut parts = num.split('.').map(|part| part.parse().ok());
+    let number = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(number)
+}
+
+/// The semver-compatible series of a version number, like `1` for `1.2.3`
+/// and `0.3` for `0.3.1`.
+fn major_series(num: &str) -> Option<String> {
+    let mut parts = num.split(['.', '-', '+']);
+    let major = parts.next()?;
+    let minor = parts.next()?;
+    let patch = parts.next()?;
+    Some(match (major, minor) {
+        ("0", "0") => format!("0.0.{patch}"),
+        ("0", minor) => format!("0.{minor}"),
+        (major, _) => major.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Analytics for versions `(num, created_at)` with ids starting at 1.
+    fn analytics_from(
+        downloads: serde_json::Value,
+        versions: &[(&str, &str)],
+    ) -> DownloadAnalytics {
+        let downloads = serde_json::from_value(downloads).unwrap();
+        let versions = versions
+            .iter()
+            .map(|(num, created_at)| (num.to_string(), created_at.parse().unwrap()))
+            .collect::<Vec<(String, DateTime<Utc>)>>();
+        DownloadAnalytics::from_parts(
+            &downloads,
+            versions
+                .iter()
+                .enumerate()
+                .map(|(i, (num, created_at))| (i as u64 + 1, num, *created_at, false)),
+        )
+    }
+
+    fn analytics() -> DownloadAnalytics {
+        analytics_from(
+            serde_json::json!({
+                "version_downloads": [
+                    {"date": "2024-01-29", "downloads": 10, "version": 1},
+                    {"date": "2024-01-29", "downloads": 30, "version": 2},
+                    {"date": "2024-01-31", "downloads": 20, "version": 2},
+                    {"date": "2024-02-05", "downloads": 40, "version": 3},
+                    {"date": "2024-02-05", "downloads": 5, "version": 9},
+                ],
+                "meta": {
+                    "extra_downloads": [{"date": "2024-01-30", "downloads": 15}],
+                },
+            }),
+            &[
+                ("0.9.1", "2023-01-01T00:00:00Z"),
+                ("1.0.0", "2023-06-01T00:00:00Z"),
+                ("1.1.0", "2024-01-01T00:00:00Z"),
+                ("2.0.0-rc.1", "2024-01-15T00:00:00Z"),
+            ],
+        )
+    }
+
+    fn downloads(series: &DownloadSeries) -> Vec<u64> {
+        series.points.iter().map(|p| p.downloads).collect()
+    }
+
+    #[test]
+    fn test_download_series() {
+        let analytics = analytics();
+
+        let daily = analytics.daily();
+        assert_eq!(daily.points.len(), 8);
+        assert_eq!(downloads(&daily), vec![40, 15, 20, 0, 0, 0, 0, 45]);
+        assert_eq!(daily.total(), 120);
+
+        let weekly = analytics.weekly();
+        assert_eq!(downloads(&weekly), vec![75, 45]);
+        assert_eq!(weekly.points[1].date.to_string(), "2024-02-05");
+
+        let monthly = analytics.monthly();
+        assert_eq!(downloads(&monthly), vec![75, 45]);
+        assert_eq!(monthly.points[0].date.to_string(), "2024-01-01");
+
+        let averages = daily.moving_average(2);
+        assert_eq!(averages.len(), 7);
+        assert_eq!(averages[0].value, 27.5);
+        assert_eq!(weekly.growth_rates()[0].value, -0.4);
+        // Growth after days without downloads is skipped.
+        assert_eq!(daily.growth_rates().len(), 3);
+    }
+
+    #[test]
+    fn test_download_breakdowns() {
+        let analytics = analytics();
+
+        let by_version = analytics
+            .by_version()
+            .into_iter()
+            .map(|t| (t.num, t.downloads))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            by_version,
+            vec![
+                (Some("1.0.0".to_string()), 50),
+                (Some("1.1.0".to_string()), 40),
+                (Some("0.9.1".to_string()), 10),
+                (None, 5),
+            ]
+        );
+
+        let by_major = analytics
+            .by_major()
+            .into_iter()
+            .map(|t| (t.major, t.downloads))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            by_major,
+            vec![("1".to_string(), 90), ("0.9".to_string(), 10)]
+        );
+
+        assert_eq!(analytics.latest_version(), Some((3, "1.1.0")));
+        assert_eq!(analytics.latest_share(), Some(40.0 / 105.0));
+    }
+
+    #[test]
+    fn test_adoption_and_stuck_majors() {
+        let daily = [
+            ("2024-03-01", [50, 40, 0]),
+            ("2024-03-02", [50, 40, 5]),
+            ("2024-03-03", [50, 30, 20]),
+            ("2024-03-04", [50, 20, 35]),
+            ("2024-03-05", [50, 10, 45]),
+        ];
+        let rows = daily
+            .iter()
+            .flat_map(|(date, downloads)| {
+                downloads.iter().enumerate().map(move |(i, downloads)| {
+                    serde_json::json!({"date": date, "downloads": downloads, "version": i + 1})
+                })
+            })
+            .collect::<Vec<_>>();
+        let analytics = analytics_from(
+            serde_json::json!({"version_downloads": rows, "meta": {"extra_downloads": []}}),
+            &[
+                ("1.0.0", "2023-01-01T00:00:00Z"),
+                ("2.0.0", "2023-06-01T00:00:00Z"),
+                ("2.1.0", "2024-03-02T12:00:00Z"),
+            ],
+        );
+
+        let adoption = analytics
+            .adoption()
+            .into_iter()
+            .map(|a| (a.num, a.previous, a.days_to_overtake))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            adoption,
+            vec![
+                ("1.0.0".to_string(), None, None),
+                // Published before the download data starts.
+                ("2.0.0".to_string(), Some("1.0.0".to_string()), None),
+                ("2.1.0".to_string(), Some("2.0.0".to_string()), Some(2)),
+            ]
+        );
+
+        let stuck = analytics.stuck_majors(0.25, 6);
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].major, "1");
+        assert_eq!(stuck[0].superseded_by, "2");
+        assert_eq!(stuck[0].share, 250.0 / 495.0);
+        assert!(analytics.stuck_majors(0.6, 6).is_empty());
+        assert!(analytics.stuck_majors(0.25, 12).is_empty());
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        assert_eq!(add_months(date, 1).unwrap().to_string(), "2024-02-29");
+        assert_eq!(add_months(date, 12).unwrap().to_string(), "2025-01-31");
+    }
+
+    #[test]
+    fn test_backport_release() {
+        let analytics = analytics_from(
+            serde_json::json!({
+                "version_downloads": [
+                    {"date": "2024-03-01", "downloads": 10, "version": 1},
+                    {"date": "2024-03-01", "downloads": 40, "version": 3},
+                    {"date": "2024-03-02", "downloads": 5, "version": 1},
+                    {"date": "2024-03-02", "downloads": 8, "version": 4},
+                    {"date": "2024-03-02", "downloads": 30, "version": 3},
+                ],
+                "meta": {"extra_downloads": []},
+            }),
+            &[
+                ("0.9.1", "2023-01-01T00:00:00Z"),
+                ("1.0.0", "2023-06-01T00:00:00Z"),
+                ("1.1.0", "2024-01-01T00:00:00Z"),
+                ("0.9.2+backport", "2024-03-02T00:00:00Z"),
+            ],
+        );
+
+        assert_eq!(analytics.latest_version(), Some((3, "1.1.0")));
+        assert_eq!(analytics.latest_share(), Some(70.0 / 93.0));
+
+        let adoption = analytics
+            .adoption()
+            .into_iter()
+            .map(|a| (a.num, a.previous, a.days_to_overtake))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            adoption,
+            vec![
+                ("0.9.1".to_string(), None, None),
+                (
+                    "0.9.2+backport".to_string(),
+                    Some("0.9.1".to_string()),
+                    Some(0)
+                ),
+                ("1.0.0".to_string(), Some("0.9.1".to_string()), None),
+                ("1.1.0".to_string(), Some("1.0.0".to_string()), None),
+            ]
+        );
+    }
+}
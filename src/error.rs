@@ -1,5 +1,8 @@
 //! Error types.
 
+use reqwest::{header::HeaderMap, StatusCode};
+use std::{convert::TryFrom, time::Duration};
+
 /// Errors returned by the api client.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -15,7 +18,170 @@ pub enum Error {
     /// JSON decoding of API response failed.
     JsonDecode(JsonDecodeError),
     /// Error returned by the crates.io API directly.
-    Api(crate::types::ApiErrors),
+    ///
+    /// Produced for successful and server error responses whose body is an
+    /// API error document. Error documents of other client errors are
+    /// reported as [`Error::BadRequest`].
+    Api {
+        /// The requested URL.
+        url: String,
+        /// The HTTP status code.
+        status: StatusCode,
+        /// The errors reported by the API.
+        errors: crate::types::ApiErrors,
+    },
+    /// The crates.io rate limit was exceeded (HTTP 429).
+    RateLimited {
+        /// The requested URL.
+        url: String,
+        /// How long the server asked us to wait before retrying, if known.
+        retry_after: Option<Duration>,
+    },
+    /// The response is not cached and the [`ResponseCache`](crate::ResponseCache)
+    /// is offline.
+    CacheMiss {
+        /// The requested URL.
+        url: String,
+    },
+    /// The server failed to handle the request (HTTP 5xx).
+    Server {
+        /// The requested URL.
+        url: String,
+        /// The HTTP status code.
+        status: StatusCode,
+        /// The raw response body.
+        body: String,
+    },
+    /// No version of a crate matches the requirement.
+    NoMatchingVersion {
+        /// The name of the crate.
+        crate_name: String,
+        /// The version requirement.
+        req: String,
+    },
+    /// The request was rejected by the server (HTTP 4xx).
+    BadRequest {
+        /// The requested URL.
+        url: String,
+        /// The HTTP status code.
+        status: StatusCode,
+        /// The errors reported by the API, empty if the body is not an API
+        /// error document.
+        errors: crate::types::ApiErrors,
+        /// The raw response body.
+        body: String,
+    },
+    /// The server answered with a status that is neither a success nor an
+    /// error, like an unfollowed redirect (HTTP 1xx or 3xx).
+    UnexpectedStatus {
+        /// The requested URL.
+        url: String,
+        /// The HTTP status code.
+        status: StatusCode,
+        /// The raw response body.
+        body: String,
+    },
+}
+
+impl Error {
+    /// Build the error for an unsuccessful HTTP response.
+    pub(crate) fn from_response(
+        url: &reqwest::Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: String,
+    ) -> Self {
+        let url = url.to_string();
+        match status {
+            StatusCode::NOT_FOUND => Error::NotFound(NotFoundError { url }),
+            StatusCode::FORBIDDEN => {
+                Error::PermissionDenied(PermissionDeniedError { url, reason: body })
+            }
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                url,
+                retry_after: parse_retry_after(headers),
+            },
+            _ if status.is_client_error() => Error::BadRequest {
+                url,
+                status,
+                errors: crate::types::ApiErrors::from_body(&body).unwrap_or_default(),
+                body,
+            },
+            _ => match crate::types::ApiErrors::from_body(&body) {
+                Some(errors) => Error::Api {
+                    url,
+                    status,
+                    errors,
+                },
+                None if status.is_server_error() => Error::Server { url, status, body },
+                None => Error::UnexpectedStatus { url, status, body },
+            },
+        }
+    }
+
+    /// The URL of the request that caused this error, if any.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Error::Http(e) => e.url().map(|u| u.as_str()),
+            Error::NotFound(e) => Some(&e.url),
+            Error::PermissionDenied(e) => Some(&e.url),
+            Error::JsonDecode(e) => Some(&e.url),
+            Error::Api { url, .. }
+            | Error::RateLimited { url, .. }
+            | Error::CacheMiss { url }
+            | Error::Server { url, .. }
+            | Error::BadRequest { url, .. }
+            | Error::UnexpectedStatus { url, .. } => Some(url),
+            Error::Url(_) | Error::NoMatchingVersion { .. } => None,
+        }
+    }
+
+    /// The HTTP status code returned by the server, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Http(e) => e.status(),
+            Error::NotFound(_) => Some(StatusCode::NOT_FOUND),
+            Error::PermissionDenied(_) => Some(StatusCode::FORBIDDEN),
+            Error::JsonDecode(e) => Some(e.status),
+            Error::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Error::Api { status, .. }
+            | Error::Server { status, .. }
+            | Error::BadRequest { status, .. }
+            | Error::UnexpectedStatus { status, .. } => Some(*status),
+            Error::Url(_) | Error::CacheMiss { .. } | Error::NoMatchingVersion { .. } => None,
+        }
+    }
+
+    /// Returns `true` if the request may succeed when retried later.
+    ///
+    /// This is the case for rate limiting, server errors, timeouts and
+    /// connection failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http(e) => e.is_timeout() || e.is_connect(),
+            Error::RateLimited { .. } | Error::Server { .. } => true,
+            Error::Api { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+/// Parse the `Retry-After` header, which is either a number of seconds or an
+/// HTTP date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let at = std::time::UNIX_EPOCH + Duration::from_secs(u64::try_from(date.timestamp()).ok()?);
+    at.duration_since(std::time::SystemTime::now()).ok()
 }
 
 impl std::fmt::Display for Error {
@@ -25,7 +191,7 @@ impl std::fmt::Display for Error {
             Error::Url(e) => e.fmt(f),
             Error::NotFound(e) => e.fmt(f),
             Error::PermissionDenied(e) => e.fmt(f),
-            Error::Api(err) => {
+            Error::Api { errors: err, .. } => {
                 let inner = if err.errors.is_empty() {
                     "Unknown API error".to_string()
                 } else {
@@ -39,6 +205,43 @@ impl std::fmt::Display for Error {
                 write!(f, "API Error ({})", inner)
             }
             Error::JsonDecode(err) => write!(f, "Could not decode API JSON response: {err}"),
+            Error::RateLimited { url, retry_after } => {
+                write!(f, "Rate limit exceeded for url '{url}'")?;
+                if let Some(delay) = retry_after {
+                    write!(f, " (retry after {}s)", delay.as_secs())?;
+                }
+                Ok(())
+            }
+            Error::CacheMiss { url } => {
+                write!(f, "Response for url '{url}' is not cached (offline mode)")
+            }
+            Error::Server { url, status, .. } => {
+                write!(f, "Server error for url '{url}': {status}")
+            }
+            Error::NoMatchingVersion { crate_name, req } => {
+                write!(f, "No version of crate '{crate_name}' matches '{req}'")
+            }
+            Error::BadRequest {
+                url,
+                status,
+                errors,
+                ..
+            } => {
+                write!(f, "Request to url '{url}' failed with {status}")?;
+                if !errors.errors.is_empty() {
+                    let inner = errors
+                        .errors
+                        .iter()
+                        .map(|err| err.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    write!(f, " ({})", inner)?;
+                }
+                Ok(())
+            }
+            Error::UnexpectedStatus { url, status, .. } => {
+                write!(f, "Unexpected response status for url '{url}': {status}")
+            }
         }
     }
 }
@@ -50,8 +253,14 @@ impl std::error::Error for Error {
             Error::Url(e) => Some(e),
             Error::NotFound(_) => None,
             Error::PermissionDenied(_) => None,
-            Error::Api(_) => None,
+            Error::Api { .. } => None,
             Error::JsonDecode(err) => Some(err),
+            Error::RateLimited { .. } => None,
+            Error::CacheMiss { .. } => None,
+            Error::Server { .. } => None,
+            Error::NoMatchingVersion { .. } => None,
+            Error::BadRequest { .. } => None,
+            Error::UnexpectedStatus { .. } => None,
         }
     }
 
@@ -83,16 +292,83 @@ impl From<url::ParseError> for Error {
 /// Error returned when the JSON returned by the API could not be decoded.
 #[derive(Debug)]
 pub struct JsonDecodeError {
-    pub(crate) message: String,
+    pub(crate) url: String,
+    pub(crate) status: StatusCode,
+    pub(crate) path: serde_path_to_error::Path,
+    pub(crate) body: String,
+    pub(crate) body_truncated: bool,
+    pub(crate) source: serde_json::Error,
+}
+
+impl JsonDecodeError {
+    /// Maximum number of bytes of the response body kept for inspection.
+    pub const MAX_BODY_LEN: usize = 8 * 1024;
+
+    pub(crate) fn new(
+        url: &reqwest::Url,
+        status: StatusCode,
+        body: &str,
+        err: serde_path_to_error::Error<serde_json::Error>,
+    ) -> Self {
+        let mut end = body.len().min(Self::MAX_BODY_LEN);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        let path = err.path().clone();
+
+        Self {
+            url: url.to_string(),
+            status,
+            path,
+            body: body[..end].to_string(),
+            body_truncated: end < body.len(),
+            source: err.into_inner(),
+        }
+    }
+
+    /// The URL of the request.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The HTTP status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The path to the value that could not be decoded.
+    pub fn path(&self) -> &serde_path_to_error::Path {
+        &self.path
+    }
+
+    /// The raw response body.
+    ///
+    /// Truncated to at most [`Self::MAX_BODY_LEN`] bytes.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Returns `true` if [`Self::body`] was truncated.
+    pub fn is_body_truncated(&self) -> bool {
+        self.body_truncated
+    }
 }
 
 impl std::fmt::Display for JsonDecodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Could not decode JSON: {}", self.message)
+        write!(
+            f,
+            "Could not decode JSON: {} (path: {})",
+            self.source, self.path
+        )
     }
 }
 
-impl std::error::Error for JsonDecodeError {}
+impl std::error::Error for JsonDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
 
 /// Error returned when a resource could not be found.
 #[derive(Debug)]
@@ -100,6 +376,13 @@ pub struct NotFoundError {
     pub(crate) url: String,
 }
 
+impl NotFoundError {
+    /// The URL of the missing resource.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
 impl std::fmt::Display for NotFoundError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Resource at url '{}' could not be found", self.url)
@@ -109,11 +392,189 @@ impl std::fmt::Display for NotFoundError {
 /// Error returned when a resource is not accessible.
 #[derive(Debug)]
 pub struct PermissionDeniedError {
+    pub(crate) url: String,
     pub(crate) reason: String,
 }
 
+impl PermissionDeniedError {
+    /// The URL of the inaccessible resource.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The reason given by the server.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
 impl std::fmt::Display for PermissionDeniedError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Permission denied: {}", self.reason)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url() -> reqwest::Url {
+        reqwest::Url::parse("https://crates.io/api/v1/crates/serde").unwrap()
+    }
+
+    #[test]
+    fn test_error_from_response_status() {
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "30".parse().unwrap());
+        let err = Error::from_response(
+            &url(),
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers,
+            String::new(),
+        );
+        match &err {
+            Error::RateLimited { retry_after, .. } => {
+                assert_eq!(*retry_after, Some(Duration::from_secs(30)))
+            }
+            other => panic!("expected RateLimited error, got {:?}", other),
+        }
+        assert!(err.is_retryable());
+        assert_eq!(err.url(), Some("https://crates.io/api/v1/crates/serde"));
+
+        let err = Error::from_response(
+            &url(),
+            StatusCode::BAD_GATEWAY,
+            &HeaderMap::new(),
+            "upstream".to_string(),
+        );
+        assert!(matches!(err, Error::Server { ref body, .. } if body == "upstream"));
+        assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+        assert!(err.is_retryable());
+
+        let err = Error::from_response(
+            &url(),
+            StatusCode::BAD_REQUEST,
+            &HeaderMap::new(),
+            "<html>bad request</html>".to_string(),
+        );
+        match &err {
+            Error::BadRequest { errors, body, .. } => {
+                assert!(errors.errors.is_empty());
+                assert_eq!(body, "<html>bad request</html>");
+            }
+            other => panic!("expected BadRequest error, got {:?}", other),
+        }
+        assert!(!err.is_retryable());
+
+        let err = Error::from_response(
+            &url(),
+            StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new(),
+            String::new(),
+        );
+        assert!(matches!(
+            err,
+            Error::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+
+        let err = Error::from_response(
+            &url(),
+            StatusCode::MOVED_PERMANENTLY,
+            &HeaderMap::new(),
+            String::new(),
+        );
+        assert!(matches!(err, Error::UnexpectedStatus { .. }));
+        assert_eq!(err.status(), Some(StatusCode::MOVED_PERMANENTLY));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_error_from_response_api_errors() {
+        let body = r#"{"errors":[{"detail":"invalid page"}]}"#;
+        for status in [StatusCode::BAD_REQUEST, StatusCode::UNPROCESSABLE_ENTITY] {
+            let err = Error::from_response(&url(), status, &HeaderMap::new(), body.to_string());
+            match &err {
+                Error::BadRequest {
+                    status: s, errors, ..
+                } => {
+                    assert_eq!(*s, status);
+                    assert_eq!(errors.errors[0].detail.as_deref(), Some("invalid page"));
+                }
+                other => panic!("expected BadRequest error, got {:?}", other),
+            }
+            assert!(err.to_string().ends_with("(invalid page)"));
+        }
+
+        for status in [StatusCode::INTERNAL_SERVER_ERROR, StatusCode::BAD_GATEWAY] {
+            let err = Error::from_response(&url(), status, &HeaderMap::new(), body.to_string());
+            match &err {
+                Error::Api {
+                    status: s, errors, ..
+                } => {
+                    assert_eq!(*s, status);
+                    assert_eq!(errors.errors[0].detail.as_deref(), Some("invalid page"));
+                }
+                other => panic!("expected Api error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_decode_error_keeps_context() {
+        #[derive(serde_derive::Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Payload {
+            items: Vec<u64>,
+        }
+
+        let body = format!(
+            r#"{{"items": [1, "two"], "padding": "{}"}}"#,
+            "x".repeat(JsonDecodeError::MAX_BODY_LEN)
+        );
+        let jd = &mut serde_json::Deserializer::from_str(&body);
+        let err = serde_path_to_error::deserialize::<_, Payload>(jd).unwrap_err();
+        let err = JsonDecodeError::new(&url(), StatusCode::OK, &body, err);
+
+        assert_eq!(err.path().to_string(), "items[1]");
+        assert_eq!(err.status(), StatusCode::OK);
+        assert_eq!(err.url(), "https://crates.io/api/v1/crates/serde");
+        assert!(err.is_body_truncated());
+        assert_eq!(err.body().len(), JsonDecodeError::MAX_BODY_LEN);
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_success_payload_with_errors_key() {
+        #[derive(serde_derive::Deserialize, Debug)]
+        struct Payload {
+            errors: Vec<String>,
+            total: u64,
+        }
+
+        let body = r#"{"errors": ["not an api error"], "total": 1}"#;
+        let payload: Payload =
+            crate::async_client::parse_response(&url(), StatusCode::OK, &HeaderMap::new(), body)
+                .unwrap();
+        assert_eq!(payload.errors.len(), 1);
+        assert_eq!(payload.total, 1);
+
+        let body = r#"{"errors": [{"detail": "boom"}]}"#;
+        let err = crate::async_client::parse_response::<Payload>(
+            &url(),
+            StatusCode::OK,
+            &HeaderMap::new(),
+            body,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Api {
+                status: StatusCode::OK,
+                ..
+            }
+        ));
+    }
+}
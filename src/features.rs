@@ -0,0 +1,403 @@
+//! Resolution of crate features.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_derive::*;
+
+use crate::types::{Dependency, DependencyKind, Version};
+
+/// The features requested for a crate version.
+///
+/// By default, the `default` feature is enabled.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeatureSelection {
+    features: BTreeSet<String>,
+    default_features: bool,
+}
+
+impl FeatureSelection {
+    /// Only the default features.
+    pub fn new() -> Self {
+        Self {
+            features: BTreeSet::new(),
+            default_features: true,
+        }
+    }
+
+    /// Enable a feature.
+    ///
+    /// Accepts the same syntax as Cargo's `--features` flag, including
+    /// `dep/feature`.
+    #[must_use]
+    pub fn feature(mut self, feature: impl Into<String>) -> Self {
+        self.features.insert(feature.into());
+        self
+    }
+
+    /// Enable multiple features.
+    #[must_use]
+    pub fn features<I, S>(mut self, features: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Enable or disable the `default` feature.
+    #[must_use]
+    pub fn default_features(mut self, enabled: bool) -> Self {
+        self.default_features = enabled;
+        self
+    }
+
+    /// The explicitly enabled features.
+    pub fn enabled(&self) -> &BTreeSet<String> {
+        &self.features
+    }
+
+    /// Whether the `default` feature is enabled.
+    pub fn uses_default_features(&self) -> bool {
+        self.default_features
+    }
+
+    /// Merge another selection into this one, like Cargo's feature
+    /// unification.
+    ///
+    /// Returns `true` if the selection changed.
+    pub fn merge(&mut self, other: &FeatureSelection) -> bool {
+        let len = self.features.len();
+        self.features.extend(other.features.iter().cloned());
+        let changed =
+            self.features.len() != len || (other.default_features && !self.default_features);
+        self.default_features |= other.default_features;
+        changed
+    }
+}
+
+impl From<&Dependency> for FeatureSelection {
+    /// The features enabled by a dependency declaration.
+    fn from(dep: &Dependency) -> Self {
+        Self::new()
+            .default_features(dep.default_features)
+            .features(dep.features.iter().cloned())
+    }
+}
+
+impl Default for FeatureSelection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An activated dependency of a [`ResolvedFeatures`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActivatedDependency {
+    /// All declarations of the dependency, for example for different targets.
+    pub declarations: Vec<Dependency>,
+    /// Whether the default features of the dependency are enabled.
+    pub default_features: bool,
+    /// The features enabled on the dependency.
+    pub features: BTreeSet<String>,
+}
+
+impl ActivatedDependency {
+    /// The features to enable on the dependency.
+    pub fn selection(&self) -> FeatureSelection {
+        FeatureSelection::new()
+            .default_features(self.default_features)
+            .features(self.features.iter().cloned())
+    }
+}
+
+/// The result of resolving a [`FeatureSelection`] for a crate version.
+///
+/// Dev-dependencies are not taken into account, because they are not used
+/// when the crate is built as a dependency.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{FeatureSelection, ResolvedFeatures, SyncClient};
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let version = client.crate_versions("reqwest")?.remove(0);
+/// let dependencies = client.crate_dependencies("reqwest", &version.num)?;
+/// let selection = FeatureSelection::new().default_features(false).feature("json");
+/// let resolved = ResolvedFeatures::resolve(&version, &dependencies, &selection);
+/// for (name, dep) in &resolved.dependencies {
+///     println!("{name}: {:?}", dep.features);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ResolvedFeatures {
+    /// The enabled features of the crate.
+    pub features: BTreeSet<String>,
+    /// The activated dependencies, by crate name.
+    pub dependencies: BTreeMap<String, ActivatedDependency>,
+    /// Requested features that do not exist.
+    pub unknown_features: BTreeSet<String>,
+}
+
+impl ResolvedFeatures {
+    /// Resolve the features of a crate version.
+    ///
+    /// Supports feature names, `dep:name`, `name/feature` and
+    /// `name?/feature` entries, as well as the implicit features of optional
+    /// dependencies.
+    pub fn resolve(
+        version: &Version,
+        dependencies: &[Dependency],
+        selection: &FeatureSelection,
+    ) -> Self {
+        Resolver::new(&version.features, dependencies).resolve(selection)
+    }
+}
+
+struct Resolver<'a> {
+    features: &'a HashMap<String, Vec<String>>,
+    dependencies: Vec<&'a Dependency>,
+    /// Optional dependencies referenced with `dep:`, which have no implicit
+    /// feature.
+    explicit: BTreeSet<&'a str>,
+    result: ResolvedFeatures,
+    /// Features of dependencies enabled with `name?/feature`.
+    weak: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(features: &'a HashMap<String, Vec<String>>, dependencies: &'a [Dependency]) -> Self {
+        let explicit = features
+            .values()
+            .flatten()
+            .filter_map(|value| value.strip_prefix("dep:"))
+            .collect();
+        Self {
+            features,
+            dependencies: dependencies
+                .iter()
+                .filter(|dep| dep.kind != DependencyKind::Dev)
+                .collect(),
+            explicit,
+            result: ResolvedFeatures::default(),
+            weak: Vec::new(),
+        }
+    }
+
+    fn resolve(mut self, selection: &'a FeatureSelection) -> ResolvedFeatures {
+        for dep in self.dependencies.clone() {
+            if !dep.optional {
+                self.activate(&dep.crate_id);
+            }
+        }
+        if selection.default_features && self.features.contains_key("default") {
+            self.enable("default");
+        }
+        for feature in &selection.features {
+            if !self.enable_value(feature) {
+                self.result.unknown_features.insert(feature.clone());
+            }
+        }
+
+        for (name, feature) in std::mem::take(&mut self.weak) {
+            if let Some(dep) = self.result.dependencies.get_mut(name) {
+                dep.features.insert(feature.to_string());
+            }
+        }
+        self.result
+    }
+
+    /// Enable a feature of the crate.
+    fn enable(&mut self, feature: &'a str) {
+        if !self.result.features.insert(feature.to_string()) {
+            return;
+        }
+        if let Some(values) = self.features.get(feature) {
+            for value in values {
+                self.enable_value(value);
+            }
+        }
+    }
+
+    /// Apply a single entry of a feature list.
+    ///
+    /// Returns `false` if the entry does not refer to anything.
+    fn enable_value(&mut self, value: &'a str) -> bool {
+        if let Some(name) = value.strip_prefix("dep:") {
+            return self.activate(name);
+        }
+
+        if let Some((name, feature)) = value.split_once('/') {
+            if let Some(name) = name.strip_suffix('?') {
+                self.weak.push((name, feature));
+                return self.has_dependency(name);
+            }
+            if !self.activate(name) {
+                return false;
+            }
+            if self.is_implicit_feature(name) {
+                self.result.features.insert(name.to_string());
+            }
+            if let Some(dep) = self.result.dependencies.get_mut(name) {
+                dep.features.insert(feature.to_string());
+            }
+            return true;
+        }
+
+        if self.features.contains_key(value) {
+            self.enable(value);
+            true
+        } else if self.is_implicit_feature(value) {
+            self.result.features.insert(value.to_string());
+            self.activate(value)
+        } else {
+            false
+        }
+    }
+
+    fn has_dependency(&self, name: &str) -> bool {
+        self.dependencies.iter().any(|dep| dep.crate_id == name)
+    }
+
+    fn is_implicit_feature(&self, name: &str) -> bool {
+        !self.explicit.contains(name)
+            && !self.features.contains_key(name)
+            && self
+                .dependencies
+                .iter()
+                .any(|dep| dep.optional && dep.crate_id == name)
+    }
+
+    /// Activate all declarations of a dependency.
+    ///
+    /// Returns `false` if there is no such dependency.
+    fn activate(&mut self, name: &str) -> bool {
+        let mut found = false;
+        for dep in &self.dependencies {
+            if dep.crate_id != name {
+                continue;
+            }
+            found = true;
+            let activated = self
+                .result
+                .dependencies
+                .entry(dep.crate_id.clone())
+                .or_insert_with(|| ActivatedDependency {
+                    declarations: Vec::new(),
+                    default_features: false,
+                    features: BTreeSet::new(),
+                });
+            if activated.declarations.iter().any(|d| d.id == dep.id) {
+                continue;
+            }
+            activated.declarations.push((*dep).clone());
+            activated.default_features |= dep.default_features;
+            activated.features.extend(dep.features.iter().cloned());
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dependency(id: u64, name: &str, kind: &str, optional: bool) -> Dependency {
+        serde_json::from_value(serde_json::json!({
+            "crate_id": name,
+            "default_features": !optional,
+            "downloads": 0,
+            "features": [],
+            "id": id,
+            "kind": kind,
+            "optional": optional,
+            "req": "^1",
+            "target": null,
+            "version_id": 1,
+        }))
+        .unwrap()
+    }
+
+    fn resolve(selection: &FeatureSelection) -> ResolvedFeatures {
+        let features = [
+            ("default", vec!["std"]),
+            ("std", vec!["serde?/std", "log/std"]),
+            ("derive", vec!["serde/derive"]),
+            ("tls", vec!["dep:rustls", "dep:webpki"]),
+        ]
+        .iter()
+        .map(|(name, values)| {
+            (
+                name.to_string(),
+                values.iter().map(|value| value.to_string()).collect(),
+            )
+        })
+        .collect();
+        let dependencies = vec![
+            dependency(1, "log", "normal", false),
+            dependency(2, "serde", "normal", true),
+            dependency(3, "rustls", "normal", true),
+            dependency(4, "webpki", "normal", true),
+            dependency(5, "libc", "build", true),
+            dependency(6, "criterion", "dev", false),
+        ];
+        Resolver::new(&features, &dependencies).resolve(selection)
+    }
+
+    fn names(resolved: &ResolvedFeatures) -> Vec<&str> {
+        resolved.dependencies.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_default_features() {
+        let resolved = resolve(&FeatureSelection::new());
+
+        assert_eq!(names(&resolved), vec!["log"]);
+        assert_eq!(
+            resolved.features.iter().collect::<Vec<_>>(),
+            vec!["default", "std"]
+        );
+        assert!(resolved.dependencies["log"].features.contains("std"));
+        assert!(resolved.unknown_features.is_empty());
+    }
+
+    #[test]
+    fn test_feature_syntax() {
+        let resolved = resolve(
+            &FeatureSelection::new()
+                .feature("derive")
+                .feature("tls")
+                .feature("libc")
+                .feature("nope"),
+        );
+
+        assert_eq!(
+            names(&resolved),
+            vec!["libc", "log", "rustls", "serde", "webpki"]
+        );
+        // `serde/derive` enables the implicit feature, `dep:` does not.
+        assert!(resolved.features.contains("serde"));
+        assert!(!resolved.features.contains("rustls"));
+        let serde = &resolved.dependencies["serde"].features;
+        assert!(serde.contains("derive") && serde.contains("std"));
+        assert_eq!(
+            resolved.unknown_features.into_iter().collect::<Vec<_>>(),
+            vec!["nope"]
+        );
+    }
+
+    #[test]
+    fn test_weak_features_without_dependency() {
+        let resolved = resolve(
+            &FeatureSelection::new()
+                .default_features(false)
+                .feature("std"),
+        );
+
+        assert_eq!(names(&resolved), vec!["log"]);
+        assert!(!resolved.features.contains("default"));
+        assert!(resolved.dependencies["log"].features.contains("std"));
+    }
+}
@@ -0,0 +1,670 @@
+//! Transitive dependency graphs.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use semver::VersionReq;
+
+use crate::{
+    error::Error,
+    features::{FeatureSelection, ResolvedFeatures},
+    types::{Dependency, DependencyKind, Version},
+    AsyncClient, SyncClient,
+};
+
+/// Index of a node in a [`DependencyGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of the node in [`DependencyGraph::nodes`].
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A resolved crate version in a [`DependencyGraph`].
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    /// The resolved version.
+    pub version: Version,
+    /// Distance from the root crate.
+    pub depth: usize,
+    /// The enabled features.
+    ///
+    /// Only set if the graph was built with
+    /// [`DependencyGraphBuilder::features`].
+    pub features: BTreeSet<String>,
+}
+
+impl GraphNode {
+    /// The name of the crate.
+    pub fn name(&self) -> &str {
+        &self.version.crate_name
+    }
+
+    /// The version number.
+    pub fn num(&self) -> &str {
+        &self.version.num
+    }
+}
+
+/// A dependency between two nodes of a [`DependencyGraph`].
+///
+/// The dependency carries the labels of the edge, like its kind, whether it
+/// is optional and the target it is limited to.
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    /// The dependent.
+    pub from: NodeId,
+    /// The dependency.
+    pub to: NodeId,
+    /// The dependency as declared by the dependent.
+    pub dependency: Dependency,
+}
+
+/// A dependency that could not be resolved to a version.
+#[derive(Debug, Clone)]
+pub struct UnresolvedDependency {
+    /// The dependent.
+    pub from: NodeId,
+    /// The dependency as declared by the dependent.
+    pub dependency: Dependency,
+}
+
+/// The transitive dependencies of a crate version.
+///
+/// Every requirement is resolved to the highest non-yanked version matching
+/// it. Each crate version is only contained once, even if it is required by
+/// multiple dependents.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{DependencyGraph, SyncClient};
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let graph = DependencyGraph::builder("crates_io_api").build(&client)?;
+/// for node in graph.nodes() {
+///     println!("{} {}", node.name(), node.num());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DependencyGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    unresolved: Vec<UnresolvedDependency>,
+}
+
+impl DependencyGraph {
+    /// Construct a new [`DependencyGraphBuilder`] for the given root crate.
+    pub fn builder(crate_name: impl Into<String>) -> DependencyGraphBuilder {
+        DependencyGraphBuilder::new(crate_name)
+    }
+
+    /// The root crate version.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Get a node.
+    pub fn node(&self, id: NodeId) -> &GraphNode {
+        &self.nodes[id.0]
+    }
+
+    /// All nodes, in the order they were discovered.
+    pub fn nodes(&self) -> &[GraphNode] {
+        &self.nodes
+    }
+
+    /// All edges.
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges
+    }
+
+    /// Dependencies that did not match any available version.
+    pub fn unresolved(&self) -> &[UnresolvedDependency] {
+        &self.unresolved
+    }
+
+    /// Find the node of a crate version.
+    pub fn find(&self, crate_name: &str, num: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|n| n.name() == crate_name && n.num() == num)
+            .map(NodeId)
+    }
+
+    /// The outgoing edges of a node.
+    pub fn dependencies_of(&self, id: NodeId) -> impl Iterator<Item = &GraphEdge> {
+        self.edges.iter().filter(move |e| e.from == id)
+    }
+
+    /// The incoming edges of a node.
+    pub fn dependents_of(&self, id: NodeId) -> impl Iterator<Item = &GraphEdge> {
+        self.edges.iter().filter(move |e| e.to == id)
+    }
+}
+
+/// Builder for a [`DependencyGraph`].
+///
+/// By default, the latest stable version of the root crate is used, and
+/// normal and build dependencies are followed, while dev-dependencies and
+/// optional dependencies are skipped.
+///
+/// With [`DependencyGraphBuilder::features`], optional dependencies are
+/// followed only if they are enabled by the features of their dependent.
+#[derive(Debug, Clone)]
+pub struct DependencyGraphBuilder {
+    crate_name: String,
+    version: Option<String>,
+    dev_dependencies: bool,
+    optional_dependencies: bool,
+    features: Option<FeatureSelection>,
+    max_depth: Option<usize>,
+}
+
+impl DependencyGraphBuilder {
+    /// Construct a new builder for the given root crate.
+    #[must_use]
+    pub fn new(crate_name: impl Into<String>) -> Self {
+        Self {
+            crate_name: crate_name.into(),
+            version: None,
+            dev_dependencies: false,
+            optional_dependencies: false,
+            features: None,
+            max_depth: None,
+        }
+    }
+
+    /// Set the exact version of the root crate.
+    #[must_use]
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Include the dev-dependencies of the root crate.
+    ///
+    /// Dev-dependencies of other crates are never included, like in Cargo.
+    #[must_use]
+    pub fn dev_dependencies(mut self, enabled: bool) -> Self {
+        self.dev_dependencies = enabled;
+        self
+    }
+
+    /// Include optional dependencies, regardless of which features enable
+    /// them.
+    #[must_use]
+    pub fn optional_dependencies(mut self, enabled: bool) -> Self {
+        self.optional_dependencies = enabled;
+        self
+    }
+
+    /// Resolve features, starting with the given features of the root crate.
+    ///
+    /// Optional dependencies are then only followed if they are activated,
+    /// and the features requested by all dependents are unified, like in
+    /// Cargo.
+    /// Overrides [`DependencyGraphBuilder::optional_dependencies`].
+    #[must_use]
+    pub fn features(mut self, features: FeatureSelection) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Stop following dependencies after the given distance from the root.
+    #[must_use]
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Build the graph with a [`SyncClient`].
+    pub fn build(&self, client: &SyncClient) -> Result<DependencyGraph, Error> {
+        let mut resolver = Resolver::new(self);
+        while let Some(fetch) = resolver.next_fetch()? {
+            match fetch {
+                Fetch::Versions(name) => {
+                    let versions = client.crate_versions(&name)?;
+                    resolver.add_versions(&name, versions);
+                }
+                Fetch::Dependencies {
+                    id,
+                    crate_name,
+                    num,
+                } => {
+                    let deps = client.crate_dependencies(&crate_name, &num)?;
+                    resolver.add_dependencies(id, deps);
+                }
+            }
+        }
+        Ok(resolver.finish())
+    }
+
+    /// Build the graph with an [`AsyncClient`].
+    pub async fn build_async(&self, client: &AsyncClient) -> Result<DependencyGraph, Error> {
+        let mut resolver = Resolver::new(self);
+        while let Some(fetch) = resolver.next_fetch()? {
+            match fetch {
+                Fetch::Versions(name) => {
+                    let versions = client.crate_versions(&name).await?;
+                    resolver.add_versions(&name, versions);
+                }
+                Fetch::Dependencies {
+                    id,
+                    crate_name,
+                    num,
+                } => {
+                    let deps = client.crate_dependencies(&crate_name, &num).await?;
+                    resolver.add_dependencies(id, deps);
+                }
+            }
+        }
+        Ok(resolver.finish())
+    }
+}
+
+/// Data the [`Resolver`] needs before it can continue.
+enum Fetch {
+    /// The versions of a crate.
+    Versions(String),
+    /// The dependencies of a node.
+    Dependencies {
+        id: NodeId,
+        crate_name: String,
+        num: String,
+    },
+}
+
+/// Graph construction state shared by the sync and async builders.
+struct Resolver<'a> {
+    options: &'a DependencyGraphBuilder,
+    /// Available versions per crate, highest first.
+    versions: HashMap<String, Vec<Version>>,
+    nodes: Vec<GraphNode>,
+    /// The unified features requested for each node.
+    selections: Vec<FeatureSelection>,
+    /// Declared dependencies of the processed nodes.
+    dependencies: HashMap<NodeId, Vec<Dependency>>,
+    edges: Vec<GraphEdge>,
+    edge_index: HashSet<(NodeId, NodeId, u64)>,
+    unresolved: Vec<UnresolvedDependency>,
+    index: HashMap<(String, String), NodeId>,
+    queue: VecDeque<NodeId>,
+    /// The node being processed, with the dependencies still to be added.
+    current: Option<(NodeId, VecDeque<(Dependency, FeatureSelection)>)>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(options: &'a DependencyGraphBuilder) -> Self {
+        Self {
+            options,
+            versions: HashMap::new(),
+            nodes: Vec::new(),
+            selections: Vec::new(),
+            dependencies: HashMap::new(),
+            edges: Vec::new(),
+            edge_index: HashSet::new(),
+            unresolved: Vec::new(),
+            index: HashMap::new(),
+            queue: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Advance the resolution as far as possible with the data at hand.
+    ///
+    /// Returns the data to fetch next, or `None` once the graph is complete.
+    fn next_fetch(&mut self) -> Result<Option<Fetch>, Error> {
+        if self.nodes.is_empty() {
+            if !self.versions.contains_key(&self.options.crate_name) {
+                return Ok(Some(Fetch::Versions(self.options.crate_name.clone())));
+            }
+            self.add_root()?;
+        }
+
+        loop {
+            if let Some((from, pending)) = &mut self.current {
+                let from = *from;
+                let name = match pending.front() {
+                    Some((dep, _)) => &dep.crate_id,
+                    None => {
+                        self.current = None;
+                        continue;
+                    }
+                };
+                if !self.versions.contains_key(name) {
+                    return Ok(Some(Fetch::Versions(name.clone())));
+                }
+                if let Some((dep, features)) = pending.pop_front() {
+                    self.add_edge(from, dep, &features);
+                }
+                continue;
+            }
+
+            let Some(id) = self.queue.pop_front() else {
+                return Ok(None);
+            };
+            if !self.dependencies.contains_key(&id) {
+                // Processed once the dependencies have been added.
+                self.queue.push_front(id);
+                let node = &self.nodes[id.0].version;
+                return Ok(Some(Fetch::Dependencies {
+                    id,
+                    crate_name: node.crate_name.clone(),
+                    num: node.num.clone(),
+                }));
+            }
+            let follow = self.follow(id);
+            self.current = Some((id, follow.into()));
+        }
+    }
+
+    fn add_dependencies(&mut self, id: NodeId, dependencies: Vec<Dependency>) {
+        self.dependencies.insert(id, dependencies);
+    }
+
+    fn add_versions(&mut self, crate_name: &str, versions: Vec<Version>) {
+        let mut versions = versions
+            .into_iter()
+            .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+            .collect::<Vec<_>>();
+        versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+        self.versions.insert(
+            crate_name.to_string(),
+            versions.into_iter().map(|(_, v)| v).collect(),
+        );
+    }
+
+    fn add_root(&mut self) -> Result<(), Error> {
+        let name = &self.options.crate_name;
+        let versions = self.versions.get(name).map(Vec::as_slice).unwrap_or(&[]);
+        let root = match &self.options.version {
+            Some(num) => versions.iter().find(|v| &v.num == num),
+            None => {
+                resolve(versions, &VersionReq::STAR).or_else(|| versions.iter().find(|v| !v.yanked))
+            }
+        };
+        let root = root.cloned().ok_or_else(|| Error::NoMatchingVersion {
+            crate_name: name.clone(),
+            req: self.options.version.clone().unwrap_or_else(|| "*".into()),
+        })?;
+        let features = self.options.features.clone().unwrap_or_default();
+        self.add_node(root, 0, &features);
+        Ok(())
+    }
+
+    fn add_node(&mut self, version: Version, depth: usize, features: &FeatureSelection) -> NodeId {
+        let key = (version.crate_name.clone(), version.num.clone());
+        if let Some(id) = self.index.get(&key).copied() {
+            // New features require processing the node again.
+            if self.selections[id.0].merge(features)
+                && self.options.features.is_some()
+                && self.dependencies.contains_key(&id)
+                && !self.queue.contains(&id)
+            {
+                self.queue.push_back(id);
+            }
+            return id;
+        }
+
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(GraphNode {
+            version,
+            depth,
+            features: BTreeSet::new(),
+        });
+        self.selections.push(features.clone());
+        self.index.insert(key, id);
+        if self.options.max_depth.is_none_or(|max| depth < max) {
+            self.queue.push_back(id);
+        }
+        id
+    }
+
+    /// The dependencies of a processed node that should be followed, with the
+    /// features to enable on them.
+    fn follow(&mut self, id: NodeId) -> Vec<(Dependency, FeatureSelection)> {
+        let deps = &self.dependencies[&id];
+        let dev = id == NodeId(0) && self.options.dev_dependencies;
+
+        if self.options.features.is_none() {
+            return deps
+                .iter()
+                .filter(|dep| match dep.kind {
+                    DependencyKind::Dev => dev,
+                    _ => !dep.optional || self.options.optional_dependencies,
+                })
+                .map(|dep| (dep.clone(), FeatureSelection::from(dep)))
+                .collect();
+        }
+
+        let resolved =
+            ResolvedFeatures::resolve(&self.nodes[id.0].version, deps, &self.selections[id.0]);
+        let mut follow = resolved
+            .dependencies
+            .values()
+            .flat_map(|activated| {
+                let features = activated.selection();
+                activated
+                    .declarations
+                    .iter()
+                    .map(move |dep| (dep.clone(), features.clone()))
+            })
+            .collect::<Vec<_>>();
+        if dev {
+            follow.extend(
+                deps.iter()
+                    .filter(|dep| dep.kind == DependencyKind::Dev)
+                    .map(|dep| (dep.clone(), FeatureSelection::from(dep))),
+            );
+        }
+        self.nodes[id.0].features = resolved.features;
+        follow
+    }
+
+    fn add_edge(&mut self, from: NodeId, dependency: Dependency, features: &FeatureSelection) {
+        let versions = self
+            .versions
+            .get(&dependency.crate_id)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let resolved = dependency
+            .version_req()
+            .ok()
+            .and_then(|req| resolve(versions, &req))
+            .cloned();
+
+        match resolved {
+            Some(version) => {
+                let depth = self.nodes[from.0].depth + 1;
+                let to = self.add_node(version, depth, features);
+                if self.edge_index.insert((from, to, dependency.id)) {
+                    self.edges.push(GraphEdge {
+                        from,
+                        to,
+                        dependency,
+                    });
+                }
+            }
+            None => {
+                let known = self
+                    .unresolved
+                    .iter()
+                    .any(|u| u.from == from && u.dependency.id == dependency.id);
+                if !known {
+                    self.unresolved
+                        .push(UnresolvedDependency { from, dependency });
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> DependencyGraph {
+        DependencyGraph {
+            nodes: self.nodes,
+            edges: self.edges,
+            unresolved: self.unresolved,
+        }
+    }
+}
+
+/// The highest non-yanked version matching the requirement.
+///
+/// `versions` must be sorted highest first.
+fn resolve<'v>(versions: &'v [Version], req: &VersionReq) -> Option<&'v Version> {
+    versions
+        .iter()
+        .filter(|v| !v.yanked)
+        .find(|v| v.semver().is_ok_and(|semver| req.matches(&semver)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(name: &str, num: &str, yanked: bool) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "crate": name,
+            "created_at": "2020-01-01T00:00:00Z",
+            "updated_at": "2020-01-01T00:00:00Z",
+            "dl_path": "",
+            "downloads": 0,
+            "features": {},
+            "id": 1,
+            "num": num,
+            "yanked": yanked,
+            "license": null,
+            "readme_path": null,
+            "links": {"dependencies": "", "version_downloads": ""},
+            "crate_size": null,
+            "published_by": null,
+            "rust_version": null,
+            "checksum": "",
+        }))
+        .unwrap()
+    }
+
+    fn dependency(id: u64, name: &str, req: &str, kind: &str, optional: bool) -> Dependency {
+        serde_json::from_value(serde_json::json!({
+            "crate_id": name,
+            "default_features": true,
+            "downloads": 0,
+            "features": [],
+            "id": id,
+            "kind": kind,
+            "optional": optional,
+            "req": req,
+            "target": null,
+            "version_id": 1,
+        }))
+        .unwrap()
+    }
+
+    /// Build the graph by answering the fetches of the resolver from a
+    /// stubbed registry.
+    fn build(options: &DependencyGraphBuilder) -> DependencyGraph {
+        let mut resolver = Resolver::new(options);
+        while let Some(fetch) = resolver.next_fetch().unwrap() {
+            match fetch {
+                Fetch::Versions(name) => {
+                    let versions = match name.as_str() {
+                        "a" => {
+                            let mut a = version("a", "1.0.0", false);
+                            a.features.insert("extra".into(), vec!["d/fast".into()]);
+                            vec![a]
+                        }
+                        "b" => vec![
+                            version("b", "1.0.0", false),
+                            version("b", "1.2.0", false),
+                            version("b", "1.3.0", true),
+                            version("b", "2.0.0", false),
+                        ],
+                        "c" => vec![version("c", "0.2.1", false)],
+                        "d" => {
+                            let mut d = version("d", "0.1.0", false);
+                            d.features.insert("fast".into(), Vec::new());
+                            vec![d]
+                        }
+                        _ => Vec::new(),
+                    };
+                    resolver.add_versions(&name, versions);
+                }
+                Fetch::Dependencies { id, crate_name, .. } => {
+                    let deps = match crate_name.as_str() {
+                        "a" => vec![
+                            dependency(1, "b", "^1", "normal", false),
+                            dependency(2, "c", "^0.2", "dev", false),
+                            dependency(3, "d", "^0.1", "normal", true),
+                            dependency(4, "e", "^1", "normal", false),
+                        ],
+                        "b" => vec![dependency(5, "c", "^0.2.1", "build", false)],
+                        _ => Vec::new(),
+                    };
+                    resolver.add_dependencies(id, deps);
+                }
+            }
+        }
+        resolver.finish()
+    }
+
+    fn names(graph: &DependencyGraph) -> Vec<String> {
+        graph
+            .nodes()
+            .iter()
+            .map(|n| format!("{} {}", n.name(), n.num()))
+            .collect()
+    }
+
+    #[test]
+    fn test_graph_resolves_requirements() {
+        let graph = build(&DependencyGraph::builder("a"));
+
+        assert_eq!(names(&graph), vec!["a 1.0.0", "b 1.2.0", "c 0.2.1"]);
+        let b = graph.find("b", "1.2.0").unwrap();
+        let edge = graph.dependencies_of(b).next().unwrap();
+        assert_eq!(edge.dependency.kind, DependencyKind::Build);
+        assert_eq!(graph.node(edge.to).depth, 2);
+        assert_eq!(graph.unresolved().len(), 1);
+        assert_eq!(graph.unresolved()[0].dependency.crate_id, "e");
+    }
+
+    #[test]
+    fn test_graph_toggles() {
+        let graph = build(
+            &DependencyGraph::builder("a")
+                .dev_dependencies(true)
+                .optional_dependencies(true)
+                .max_depth(1),
+        );
+
+        assert_eq!(
+            names(&graph),
+            vec!["a 1.0.0", "b 1.2.0", "c 0.2.1", "d 0.1.0"]
+        );
+        let c = graph.find("c", "0.2.1").unwrap();
+        assert_eq!(graph.dependents_of(c).count(), 1);
+        assert!(graph
+            .dependencies_of(graph.root())
+            .any(|e| e.dependency.optional));
+    }
+
+    #[test]
+    fn test_graph_features() {
+        let graph = build(&DependencyGraph::builder("a").features(FeatureSelection::new()));
+        assert_eq!(names(&graph), vec!["a 1.0.0", "b 1.2.0", "c 0.2.1"]);
+
+        let graph = build(
+            &DependencyGraph::builder("a").features(FeatureSelection::new().feature("extra")),
+        );
+        assert_eq!(
+            names(&graph),
+            vec!["a 1.0.0", "b 1.2.0", "d 0.1.0", "c 0.2.1"]
+        );
+        let d = graph.node(graph.find("d", "0.1.0").unwrap());
+        assert!(d.features.contains("fast"));
+        assert!(graph.node(graph.root()).features.contains("extra"));
+    }
+}
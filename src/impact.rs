@@ -0,0 +1,515 @@
+//! Impact analysis of new releases on the dependents of a crate.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use futures::{prelude::*, stream::BoxStream};
+use serde_derive::*;
+
+use crate::{
+    error::Error,
+    types::{CratesQuery, Dependency, ReverseDependenciesQuery, ReverseDependency},
+    AsyncClient, SyncClient,
+};
+
+/// The maximum page size of the crates listing.
+const CRATES_PER_PAGE: usize = 100;
+
+/// A crate that directly or transitively depends on the analyzed crate.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImpactedDependent {
+    /// The name of the dependent.
+    pub name: String,
+    /// The version of the dependent that was inspected.
+    pub version: String,
+    /// Distance from the analyzed crate. Direct dependents have depth 1.
+    pub depth: usize,
+    /// The crate the dependent depends on.
+    pub via: String,
+    /// The version requirement on `via`.
+    pub req: String,
+    /// Whether the requirement accepts the proposed version.
+    ///
+    /// Only set for direct dependents.
+    pub accepts_proposed: Option<bool>,
+    /// All-time downloads of the dependent.
+    pub downloads: u64,
+}
+
+/// The result of an [`ImpactAnalysis`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImpactReport {
+    /// The analyzed crate.
+    pub crate_name: String,
+    /// The proposed new version.
+    pub proposed: String,
+    /// The dependents, most downloaded first.
+    pub dependents: Vec<ImpactedDependent>,
+    /// Whether the walk was stopped by the budget before all dependents up
+    /// to the maximum depth were found.
+    pub truncated: bool,
+}
+
+impl ImpactReport {
+    /// Direct dependents whose requirement accepts the proposed version, and
+    /// who would receive it with a plain `cargo update`.
+    pub fn accepting(&self) -> impl Iterator<Item = &ImpactedDependent> {
+        self.dependents
+            .iter()
+            .filter(|d| d.accepts_proposed == Some(true))
+    }
+
+    /// Direct dependents whose requirement rejects the proposed version, and
+    /// who would have to update their manifest to use it.
+    pub fn rejecting(&self) -> impl Iterator<Item = &ImpactedDependent> {
+        self.dependents
+            .iter()
+            .filter(|d| d.accepts_proposed == Some(false))
+    }
+}
+
+/// Walks the reverse dependencies of a crate breadth-first to estimate the
+/// impact of a new release.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{ImpactAnalysis, SyncClient};
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let proposed = semver::Version::parse("0.13.0")?;
+/// let report = ImpactAnalysis::new("crates_io_api", proposed)
+///     .max_depth(2)
+///     .run(&client)?;
+/// for dependent in report.rejecting() {
+///     println!("{} ({} downloads) requires {}", dependent.name, dependent.downloads, dependent.req);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImpactAnalysis {
+    crate_name: String,
+    proposed: semver::Version,
+    max_depth: usize,
+    max_dependents: usize,
+    query: ReverseDependenciesQuery,
+}
+
+impl ImpactAnalysis {
+    /// Analyze a proposed new version of a crate.
+    ///
+    /// By default, only direct dependents are inspected, with a budget of
+    /// 1000 dependents. Each dependent is inspected in the highest of its
+    /// versions that were listed before the budget was used up.
+    pub fn new(crate_name: impl Into<String>, proposed: semver::Version) -> Self {
+        Self {
+            crate_name: crate_name.into(),
+            proposed,
+            max_depth: 1,
+            max_dependents: 1000,
+            query: ReverseDependenciesQuery::default(),
+        }
+    }
+
+    /// Follow dependents of dependents up to the given depth.
+    #[must_use]
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Stop after the given number of dependents have been found.
+    ///
+    /// Reverse dependencies are listed lazily, so this also bounds the number
+    /// of pages that are fetched.
+    #[must_use]
+    pub fn max_dependents(mut self, max: usize) -> Self {
+        self.max_dependents = max;
+        self
+    }
+
+    /// Set the query used for listing reverse dependencies, for example to
+    /// skip dev-dependencies.
+    ///
+    /// With [`latest_only`](crate::ReverseDependenciesQueryBuilder::latest_only),
+    /// the whole listing of each crate is fetched before the budget applies.
+    #[must_use]
+    pub fn query(mut self, query: ReverseDependenciesQuery) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Run the analysis with a [`SyncClient`].
+    pub fn run(&self, client: &SyncClient) -> Result<ImpactReport, Error> {
+        self.run_with(
+            |name, visit| {
+                for dep in client.crate_reverse_dependencies_iter(name, self.query.clone()) {
+                    if !visit(dep?) {
+                        break;
+                    }
+                }
+                Ok(())
+            },
+            |ids| {
+                let page = client.crates(crates_query(ids))?;
+                Ok(page
+                    .crates
+                    .into_iter()
+                    .map(|c| (c.name, c.downloads))
+                    .collect())
+            },
+        )
+    }
+
+    /// Run the analysis with an [`AsyncClient`].
+    pub async fn run_async(&self, client: &AsyncClient) -> Result<ImpactReport, Error> {
+        self.run_async_with(
+            |name| {
+                client
+                    .crate_reverse_dependencies_stream(name, self.query.clone())
+                    .boxed()
+            },
+            |ids| {
+                let query = crates_query(ids);
+                async move {
+                    let page = client.crates(query).await?;
+                    Ok(page
+                        .crates
+                        .into_iter()
+                        .map(|c| (c.name, c.downloads))
+                        .collect())
+                }
+            },
+        )
+        .await
+    }
+
+    /// Run the walk with `dependents`, which passes the dependents of a crate
+    /// to `visit` until it returns `false`, and `downloads`, which looks up
+    /// the downloads of a set of crates.
+    fn run_with<D, L>(&self, mut dependents: D, mut downloads: L) -> Result<ImpactReport, Error>
+    where
+        D: FnMut(&str, &mut dyn FnMut(ReverseDependency) -> bool) -> Result<(), Error>,
+        L: FnMut(&[String]) -> Result<Vec<(String, u64)>, Error>,
+    {
+        let mut walk = Walk::new(self);
+        while let Some((name, depth)) = walk.next_crate() {
+            dependents(&name, &mut |dep| walk.record(&name, depth, dep))?;
+        }
+
+        let mut totals = HashMap::new();
+        for ids in walk.names().chunks(CRATES_PER_PAGE) {
+            totals.extend(downloads(ids)?);
+        }
+        Ok(walk.finish(&totals))
+    }
+
+    /// Async version of [`run_with`](Self::run_with), with `dependents`
+    /// listing the dependents of a crate as a stream.
+    async fn run_async_with<D, L, F>(
+        &self,
+        mut dependents: D,
+        mut downloads: L,
+    ) -> Result<ImpactReport, Error>
+    where
+        D: FnMut(&str) -> BoxStream<'static, Result<ReverseDependency, Error>>,
+        L: FnMut(&[String]) -> F,
+        F: Future<Output = Result<Vec<(String, u64)>, Error>>,
+    {
+        let mut walk = Walk::new(self);
+        while let Some((name, depth)) = walk.next_crate() {
+            let mut deps = dependents(&name);
+            while let Some(dep) = deps.try_next().await? {
+                if !walk.record(&name, depth, dep) {
+                    break;
+                }
+            }
+        }
+
+        let mut totals = HashMap::new();
+        for ids in walk.names().chunks(CRATES_PER_PAGE) {
+            totals.extend(downloads(ids).await?);
+        }
+        Ok(walk.finish(&totals))
+    }
+}
+
+fn crates_query(ids: &[String]) -> CratesQuery {
+    CratesQuery::builder()
+        .ids(ids.to_vec())
+        .page_size(CRATES_PER_PAGE as u64)
+        .build()
+}
+
+/// Breadth-first walk state shared by the sync and async analysis.
+struct Walk<'a> {
+    analysis: &'a ImpactAnalysis,
+    queue: VecDeque<(String, usize)>,
+    visited: HashSet<String>,
+    dependents: Vec<ImpactedDependent>,
+    /// Index of each dependent in `dependents`.
+    index: HashMap<String, usize>,
+    truncated: bool,
+}
+
+impl<'a> Walk<'a> {
+    fn new(analysis: &'a ImpactAnalysis) -> Self {
+        let mut visited = HashSet::new();
+        visited.insert(analysis.crate_name.clone());
+        Self {
+            analysis,
+            queue: VecDeque::from(vec![(analysis.crate_name.clone(), 0)]),
+            visited,
+            dependents: Vec::new(),
+            index: HashMap::new(),
+            truncated: false,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.analysis
+            .max_dependents
+            .saturating_sub(self.dependents.len())
+    }
+
+    /// The next crate whose dependents should be listed.
+    fn next_crate(&mut self) -> Option<(String, usize)> {
+        if self.remaining() == 0 {
+            self.truncated |= !self.queue.is_empty();
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    /// Record a dependent of `via`.
+    ///
+    /// Returns `false` once the budget is used up and the listing of `via`
+    /// can be stopped.
+    fn record(&mut self, via: &str, depth: usize, dep: ReverseDependency) -> bool {
+        let depth = depth + 1;
+        let name = dep.crate_version.crate_name.clone();
+        if !self.visited.insert(name.clone()) {
+            self.upgrade(via, dep);
+            return true;
+        }
+        if self.remaining() == 0 {
+            self.truncated = true;
+            return false;
+        }
+
+        if depth < self.analysis.max_depth {
+            self.queue.push_back((name.clone(), depth));
+        }
+        let accepts_proposed = self.accepts_proposed(depth, &dep.dependency);
+        self.index.insert(name.clone(), self.dependents.len());
+        self.dependents.push(ImpactedDependent {
+            name,
+            version: dep.crate_version.num,
+            depth,
+            via: via.to_string(),
+            req: dep.dependency.req,
+            accepts_proposed,
+            downloads: 0,
+        });
+        true
+    }
+
+    /// Replace a dependent of `via` that was found in an older version.
+    fn upgrade(&mut self, via: &str, dep: ReverseDependency) {
+        let Some(&index) = self.index.get(&dep.crate_version.crate_name) else {
+            return;
+        };
+        let newer = match (
+            self.dependents[index].version.parse::<semver::Version>(),
+            dep.crate_version.semver(),
+        ) {
+            (Ok(found), Ok(version)) => version > found,
+            _ => false,
+        };
+        if self.dependents[index].via != via || !newer {
+            return;
+        }
+
+        let accepts_proposed = self.accepts_proposed(self.dependents[index].depth, &dep.dependency);
+        let dependent = &mut self.dependents[index];
+        dependent.version = dep.crate_version.num;
+        dependent.req = dep.dependency.req;
+        dependent.accepts_proposed = accepts_proposed;
+    }
+
+    /// Whether the requirement of a direct dependent accepts the proposed
+    /// version.
+    fn accepts_proposed(&self, depth: usize, dependency: &Dependency) -> Option<bool> {
+        if depth == 1 {
+            dependency
+                .version_req()
+                .ok()
+                .map(|req| req.matches(&self.analysis.proposed))
+        } else {
+            None
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.dependents.iter().map(|d| d.name.clone()).collect()
+    }
+
+    fn finish(mut self, downloads: &HashMap<String, u64>) -> ImpactReport {
+        for dependent in &mut self.dependents {
+            dependent.downloads = downloads.get(&dependent.name).copied().unwrap_or(0);
+        }
+        self.dependents
+            .sort_by(|a, b| b.downloads.cmp(&a.downloads).then(a.depth.cmp(&b.depth)));
+
+        ImpactReport {
+            crate_name: self.analysis.crate_name.clone(),
+            proposed: self.analysis.proposed.to_string(),
+            dependents: self.dependents,
+            truncated: self.truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rdep(name: &str, num: &str, on: &str, req: &str) -> ReverseDependency {
+        serde_json::from_value(serde_json::json!({
+            "crate_version": {
+                "crate": name,
+                "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-01T00:00:00Z",
+                "dl_path": "",
+                "downloads": 0,
+                "features": {},
+                "id": 1,
+                "num": num,
+                "yanked": false,
+                "license": null,
+                "readme_path": null,
+                "links": {"dependencies": "", "version_downloads": ""},
+                "crate_size": null,
+                "published_by": null,
+                "rust_version": null,
+                "checksum": "",
+            },
+            "dependency": {
+                "crate_id": on,
+                "default_features": true,
+                "downloads": 0,
+                "features": [],
+                "id": 1,
+                "kind": "normal",
+                "optional": false,
+                "req": req,
+                "target": null,
+                "version_id": 1,
+            },
+        }))
+        .unwrap()
+    }
+
+    /// The reverse dependencies of the stubbed registry.
+    fn dependents(name: &str) -> Vec<ReverseDependency> {
+        match name {
+            "core" => vec![
+                rdep("app", "1.0.0", "core", "^1.2"),
+                rdep("lib", "0.1.0", "core", "^1"),
+                rdep("tool", "1.0.0", "core", "=1.2.3"),
+                rdep("lib", "0.1.1", "core", "^2"),
+            ],
+            "lib" => vec![
+                rdep("app", "1.0.0", "lib", "^0.1"),
+                rdep("cli", "1.0.0", "lib", "^0.1"),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    fn downloads(ids: &[String]) -> Result<Vec<(String, u64)>, Error> {
+        let downloads = [("app", 10), ("lib", 500), ("tool", 5), ("cli", 50)];
+        Ok(downloads
+            .iter()
+            .filter(|(name, _)| ids.iter().any(|id| id == name))
+            .map(|(name, downloads)| (name.to_string(), *downloads))
+            .collect())
+    }
+
+    /// Run the analysis against the stubbed registry, returning the report
+    /// and the number of listed dependents.
+    fn run(analysis: &ImpactAnalysis) -> (ImpactReport, usize) {
+        let mut listed = 0;
+        let report = analysis
+            .run_with(
+                |name, visit| {
+                    for dep in dependents(name) {
+                        listed += 1;
+                        if !visit(dep) {
+                            break;
+                        }
+                    }
+                    Ok(())
+                },
+                downloads,
+            )
+            .unwrap();
+        (report, listed)
+    }
+
+    #[test]
+    fn test_impact_report() {
+        let proposed = semver::Version::parse("2.0.0").unwrap();
+        let (report, _) = run(&ImpactAnalysis::new("core", proposed).max_depth(2));
+
+        let ranked = report
+            .dependents
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.accepts_proposed))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranked,
+            vec![
+                ("lib", "0.1.1", Some(true)),
+                ("cli", "1.0.0", None),
+                ("app", "1.0.0", Some(false)),
+                ("tool", "1.0.0", Some(false)),
+            ]
+        );
+        assert_eq!(report.dependents[1].depth, 2);
+        assert_eq!(report.rejecting().count(), 2);
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn test_impact_budget() {
+        let proposed = semver::Version::parse("2.0.0").unwrap();
+        let (report, listed) = run(&ImpactAnalysis::new("core", proposed)
+            .max_depth(2)
+            .max_dependents(2));
+
+        assert_eq!(report.dependents.len(), 2);
+        assert!(report.truncated);
+        // The listing stops at the first dependent over the budget.
+        assert_eq!(listed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_impact_report_async() {
+        let proposed = semver::Version::parse("2.0.0").unwrap();
+        let report = ImpactAnalysis::new("core", proposed)
+            .max_depth(2)
+            .run_async_with(
+                |name| stream::iter(dependents(name).into_iter().map(Ok)).boxed(),
+                |ids| future::ready(downloads(ids)),
+            )
+            .await
+            .unwrap();
+
+        let names = report
+            .dependents
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["lib", "cli", "app", "tool"]);
+    }
+}
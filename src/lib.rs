@@ -45,13 +45,56 @@
 #![deny(missing_docs)]
 
 mod async_client;
+mod cache;
+mod compare;
+mod downloads;
 mod error;
+mod features;
+#[cfg(feature = "semver")]
+mod graph;
+#[cfg(feature = "semver")]
+mod impact;
+mod license;
+#[cfg(feature = "lockfile")]
+mod lockfile;
+#[cfg(feature = "semver")]
+mod msrv;
+mod pagination;
+mod rate_limit;
 mod sync_client;
 mod types;
 
 pub use crate::{
-    async_client::Client as AsyncClient,
-    error::{Error, NotFoundError, PermissionDeniedError},
-    sync_client::SyncClient,
+    async_client::{Client as AsyncClient, CrateStream, FullCrateOptions, FullCrateProgress},
+    cache::{CacheStorage, CachedResponse, DiskCache, Endpoint, MemoryCache, ResponseCache},
+    compare::{ComparedCrate, CrateComparison},
+    downloads::{
+        DownloadAnalytics, DownloadPoint, DownloadSeries, MajorDownloadTotal, ReleaseAdoption,
+        SeriesValue, StuckMajor, VersionDownloadTotal,
+    },
+    error::{Error, JsonDecodeError, NotFoundError, PermissionDeniedError},
+    features::{ActivatedDependency, FeatureSelection, ResolvedFeatures},
+    license::{
+        ComplianceReport, LicenseExpr, LicenseParseError, LicensePolicy, LicenseReq,
+        LicenseViolation, LicensedCrate, UnparseableLicense,
+    },
+    pagination::{Page, PageCursor, Paginated, PaginatedIter, PaginationCheckpoint},
+    rate_limit::{FixedIntervalLimiter, RateLimiter, TokenBucketLimiter},
+    sync_client::{CrateIter, SyncClient},
     types::*,
 };
+
+#[cfg(feature = "semver")]
+pub use crate::graph::{
+    DependencyGraph, DependencyGraphBuilder, GraphEdge, GraphNode, NodeId, UnresolvedDependency,
+};
+#[cfg(feature = "semver")]
+pub use crate::impact::{ImpactAnalysis, ImpactReport, ImpactedDependent};
+#[cfg(feature = "lockfile")]
+pub use crate::lockfile::{
+    AuditReport, LockedPackage, Lockfile, Manifest, ManifestDependency, ManifestError, PackageAudit,
+};
+#[cfg(feature = "semver")]
+pub use crate::msrv::{MsrvCheck, MsrvEntry, MsrvReport, MsrvStatus};
+#[cfg(feature = "file-lock")]
+pub use crate::rate_limit::FileLockLimiter;
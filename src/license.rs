@@ -0,0 +1,637 @@
+//! SPDX license expressions and license policies.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_derive::*;
+
+use crate::types::{FullCrate, FullVersion, Version};
+
+/// A single license of a [`LicenseExpr`], like `Apache-2.0 WITH LLVM-exception`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LicenseReq {
+    /// The SPDX license identifier, or a `LicenseRef-` reference.
+    pub id: String,
+    /// Whether later versions of the license are accepted (`+`).
+    pub or_later: bool,
+    /// The SPDX exception identifier after `WITH`.
+    pub exception: Option<String>,
+}
+
+/// A parsed SPDX license expression.
+///
+/// The legacy `/` separator used by older crates (`MIT/Apache-2.0`) is
+/// treated as `OR`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LicenseExpr {
+    /// A single license.
+    License(LicenseReq),
+    /// All of the expressions apply.
+    And(Vec<LicenseExpr>),
+    /// Any of the expressions may be chosen.
+    Or(Vec<LicenseExpr>),
+}
+
+/// Error returned for invalid SPDX license expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LicenseParseError {
+    expression: String,
+    reason: String,
+}
+
+impl LicenseParseError {
+    /// The expression that failed to parse.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Why the expression is invalid.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl std::fmt::Display for LicenseParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid license expression '{}': {}",
+            self.expression, self.reason
+        )
+    }
+}
+
+impl std::error::Error for LicenseParseError {}
+
+impl LicenseExpr {
+    /// Parse an SPDX license expression.
+    pub fn parse(expression: &str) -> Result<Self, LicenseParseError> {
+        let error = |reason: String| LicenseParseError {
+            expression: expression.to_string(),
+            reason,
+        };
+
+        let mut parser = Parser {
+            tokens: tokenize(expression),
+            pos: 0,
+        };
+        let expr = parser.or().map_err(error)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(token) => Err(error(format!("unexpected '{token}'"))),
+        }
+    }
+
+    /// All licenses in the expression.
+    pub fn licenses(&self) -> Vec<&LicenseReq> {
+        match self {
+            LicenseExpr::License(req) => vec![req],
+            LicenseExpr::And(exprs) | LicenseExpr::Or(exprs) => {
+                exprs.iter().flat_map(LicenseExpr::licenses).collect()
+            }
+        }
+    }
+
+    /// Evaluate the expression, given which licenses are acceptable.
+    pub fn evaluate(&self, accept: &impl Fn(&LicenseReq) -> bool) -> bool {
+        match self {
+            LicenseExpr::License(req) => accept(req),
+            LicenseExpr::And(exprs) => exprs.iter().all(|e| e.evaluate(accept)),
+            LicenseExpr::Or(exprs) => exprs.iter().any(|e| e.evaluate(accept)),
+        }
+    }
+}
+
+impl std::str::FromStr for LicenseExpr {
+    type Err = LicenseParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for LicenseExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |f: &mut std::fmt::Formatter<'_>, exprs: &[LicenseExpr], op: &str| {
+            for (index, expr) in exprs.iter().enumerate() {
+                if index > 0 {
+                    write!(f, " {op} ")?;
+                }
+                match expr {
+                    LicenseExpr::License(_) => write!(f, "{expr}")?,
+                    _ => write!(f, "({expr})")?,
+                }
+            }
+            Ok(())
+        };
+
+        match self {
+            LicenseExpr::License(req) => {
+                write!(f, "{}", req.id)?;
+                if req.or_later {
+                    write!(f, "+")?;
+                }
+                if let Some(exception) = &req.exception {
+                    write!(f, " WITH {exception}")?;
+                }
+                Ok(())
+            }
+            LicenseExpr::And(exprs) => join(f, exprs, "AND"),
+            LicenseExpr::Or(exprs) => join(f, exprs, "OR"),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in expression.chars() {
+        match c {
+            '(' | ')' | '/' => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<LicenseExpr, String> {
+        let mut exprs = vec![self.and()?];
+        while matches!(self.peek(), Some("OR") | Some("/")) {
+            self.pos += 1;
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            LicenseExpr::Or(exprs)
+        })
+    }
+
+    fn and(&mut self) -> Result<LicenseExpr, String> {
+        let mut exprs = vec![self.primary()?];
+        while self.peek() == Some("AND") {
+            self.pos += 1;
+            exprs.push(self.primary()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            LicenseExpr::And(exprs)
+        })
+    }
+
+    fn primary(&mut self) -> Result<LicenseExpr, String> {
+        match self.next().as_deref() {
+            Some("(") => {
+                let expr = self.or()?;
+                match self.next().as_deref() {
+                    Some(")") => Ok(expr),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(token @ (")" | "/" | "AND" | "OR" | "WITH")) => {
+                Err(format!("unexpected '{token}'"))
+            }
+            Some(token) => {
+                let (id, or_later) = match token.strip_suffix('+') {
+                    Some(id) => (id, true),
+                    None => (token, false),
+                };
+                if !is_valid_id(id) {
+                    return Err(format!("invalid license identifier '{token}'"));
+                }
+
+                let exception = if self.peek() == Some("WITH") {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(exception) if is_valid_id(&exception) => Some(exception),
+                        _ => return Err("missing exception after 'WITH'".to_string()),
+                    }
+                } else {
+                    None
+                };
+                Ok(LicenseExpr::License(LicenseReq {
+                    id: id.to_string(),
+                    or_later,
+                    exception,
+                }))
+            }
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == ':')
+}
+
+/// A crate version whose license should be checked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LicensedCrate {
+    /// The name of the crate.
+    pub name: String,
+    /// The version number.
+    pub version: String,
+    /// The SPDX license expression.
+    pub license: Option<String>,
+}
+
+impl From<&Version> for LicensedCrate {
+    fn from(version: &Version) -> Self {
+        Self {
+            name: version.crate_name.clone(),
+            version: version.num.clone(),
+            license: version.license.clone(),
+        }
+    }
+}
+
+impl From<&FullVersion> for LicensedCrate {
+    fn from(version: &FullVersion) -> Self {
+        Self {
+            name: version.crate_name.clone(),
+            version: version.num.clone(),
+            license: version.license.clone(),
+        }
+    }
+}
+
+impl From<&FullCrate> for LicensedCrate {
+    /// Uses the newest version of the crate.
+    fn from(krate: &FullCrate) -> Self {
+        Self {
+            name: krate.name.clone(),
+            version: krate
+                .versions
+                .first()
+                .map_or_else(|| krate.max_version.clone(), |v| v.num.clone()),
+            license: krate.license.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "semver")]
+impl From<&crate::graph::GraphNode> for LicensedCrate {
+    fn from(node: &crate::graph::GraphNode) -> Self {
+        Self::from(&node.version)
+    }
+}
+
+#[cfg(feature = "semver")]
+impl From<&crate::graph::DependencyGraph> for Vec<LicensedCrate> {
+    /// All crate versions of the graph, including the root.
+    fn from(graph: &crate::graph::DependencyGraph) -> Self {
+        graph.nodes().iter().map(LicensedCrate::from).collect()
+    }
+}
+
+#[cfg(feature = "lockfile")]
+impl From<&crate::lockfile::PackageAudit> for LicensedCrate {
+    fn from(audit: &crate::lockfile::PackageAudit) -> Self {
+        Self {
+            name: audit.name.clone(),
+            version: audit
+                .locked
+                .clone()
+                .or_else(|| audit.newest_compatible.clone())
+                .unwrap_or_default(),
+            license: audit.license.clone(),
+        }
+    }
+}
+
+/// Decides which licenses are acceptable.
+///
+/// A license is accepted if it is not denied, and it is either allowed or
+/// the allow list is empty. Exceptions accept additional licenses for
+/// individual crates.
+///
+/// Licenses are listed by their SPDX identifier, which also covers the
+/// license with any `WITH` exception, since exceptions only grant additional
+/// permissions. A single combination can be listed as well, like
+/// `"GPL-2.0 WITH Classpath-exception-2.0"`.
+/// A license with a `+` or `-or-later` suffix is accepted if the listed
+/// version or any later version of the license that is listed is accepted.
+///
+/// ```rust
+/// use crates_io_api::{LicensedCrate, LicensePolicy};
+///
+/// let policy = LicensePolicy::new()
+///     .allow(["MIT", "Apache-2.0"])
+///     .deny(["GPL-3.0"])
+///     .exception("ring", ["ISC", "OpenSSL"]);
+/// let report = policy.evaluate(vec![LicensedCrate {
+///     name: "demo".into(),
+///     version: "1.0.0".into(),
+///     license: Some("MIT OR Apache-2.0".into()),
+/// }]);
+/// assert!(report.is_compliant());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LicensePolicy {
+    allow: BTreeSet<String>,
+    deny: BTreeSet<String>,
+    exceptions: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LicensePolicy {
+    /// A policy accepting every license.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow licenses.
+    #[must_use]
+    pub fn allow<I, S>(mut self, licenses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow.extend(licenses.into_iter().map(Into::into));
+        self
+    }
+
+    /// Deny licenses, even if they are allowed.
+    #[must_use]
+    pub fn deny<I, S>(mut self, licenses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deny.extend(licenses.into_iter().map(Into::into));
+        self
+    }
+
+    /// Accept additional licenses for a single crate.
+    #[must_use]
+    pub fn exception<I, S>(mut self, crate_name: impl Into<String>, licenses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exceptions
+            .entry(crate_name.into())
+            .or_default()
+            .extend(licenses.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether a license is acceptable for the given crate.
+    pub fn accepts(&self, crate_name: &str, license: &LicenseReq) -> bool {
+        let (id, or_later) = match license.id.strip_suffix("-or-later") {
+            Some(id) => (id, true),
+            None => (license.id.as_str(), license.or_later),
+        };
+        if self.accepts_id(crate_name, &license.id, license.exception.as_deref()) {
+            return true;
+        }
+        if !or_later {
+            return false;
+        }
+
+        // Any later version of the license may be chosen, but only versions
+        // named by the policy can be accepted.
+        let Some((family, version)) = license_version(id) else {
+            return self.accepts_id(crate_name, id, license.exception.as_deref());
+        };
+        let mut choices: BTreeSet<&str> = self
+            .allow
+            .iter()
+            .chain(self.exceptions.get(crate_name).into_iter().flatten())
+            .map(|name| name.split(" WITH ").next().unwrap_or_default().trim())
+            .filter(|name| license_version(name).is_some_and(|(f, v)| f == family && v >= version))
+            .collect();
+        choices.insert(id);
+        choices
+            .into_iter()
+            .any(|choice| self.accepts_id(crate_name, choice, license.exception.as_deref()))
+    }
+
+    fn accepts_id(&self, crate_name: &str, id: &str, exception: Option<&str>) -> bool {
+        let with = exception.map(|exception| format!("{id} WITH {exception}"));
+        let listed = |ids: &BTreeSet<String>| {
+            ids.contains(id) || with.as_ref().is_some_and(|with| ids.contains(with))
+        };
+        let excepted = self.exceptions.get(crate_name).is_some_and(listed);
+        excepted || (!listed(&self.deny) && (self.allow.is_empty() || listed(&self.allow)))
+    }
+
+    /// Check the licenses of a set of crates.
+    pub fn evaluate<I, C>(&self, crates: I) -> ComplianceReport
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<LicensedCrate>,
+    {
+        let mut report = ComplianceReport::default();
+        for krate in crates {
+            let krate = krate.into();
+            let expr = match krate.license.as_deref().map(str::trim) {
+                None | Some("") => {
+                    report.missing.push(krate);
+                    continue;
+                }
+                Some(license) => LicenseExpr::parse(license),
+            };
+            let expr = match expr {
+                Ok(expr) => expr,
+                Err(err) => {
+                    report.unparseable.push(UnparseableLicense {
+                        error: err.reason,
+                        krate,
+                    });
+                    continue;
+                }
+            };
+
+            if expr.evaluate(&|req| self.accepts(&krate.name, req)) {
+                report.compliant.push(krate);
+            } else {
+                let rejected = expr
+                    .licenses()
+                    .into_iter()
+                    .filter(|req| !self.accepts(&krate.name, req))
+                    .map(|req| req.id.clone())
+                    .collect();
+                report.violations.push(LicenseViolation { krate, rejected });
+            }
+        }
+        report
+    }
+}
+
+/// A crate whose license is not accepted by a [`LicensePolicy`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LicenseViolation {
+    /// The crate.
+    #[serde(rename = "crate")]
+    pub krate: LicensedCrate,
+    /// The licenses of the expression that were not accepted.
+    pub rejected: Vec<String>,
+}
+
+/// A crate with a license expression that could not be parsed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UnparseableLicense {
+    /// The crate.
+    #[serde(rename = "crate")]
+    pub krate: LicensedCrate,
+    /// Why the expression is invalid.
+    pub error: String,
+}
+
+/// Split a license identifier like `GPL-2.0-only` into its family and
+/// version, `("GPL", [2, 0])`.
+fn license_version(id: &str) -> Option<(&str, Vec<u64>)> {
+    let id = id.strip_suffix("-only").unwrap_or(id);
+    let (family, version) = id.rsplit_once('-')?;
+    let version = version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    Some((family, version))
+}
+
+/// The result of [`LicensePolicy::evaluate`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ComplianceReport {
+    /// Crates with an accepted license.
+    pub compliant: Vec<LicensedCrate>,
+    /// Crates with a license that is not accepted.
+    pub violations: Vec<LicenseViolation>,
+    /// Crates without a license.
+    pub missing: Vec<LicensedCrate>,
+    /// Crates with an invalid license expression.
+    pub unparseable: Vec<UnparseableLicense>,
+}
+
+impl ComplianceReport {
+    /// Returns `true` if all crates have a valid and accepted license.
+    pub fn is_compliant(&self) -> bool {
+        self.violations.is_empty() && self.missing.is_empty() && self.unparseable.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn licensed(name: &str, license: Option<&str>) -> LicensedCrate {
+        LicensedCrate {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            license: license.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        for (input, output) in [
+            ("MIT", "MIT"),
+            ("MIT/Apache-2.0", "MIT OR Apache-2.0"),
+            (
+                "(MIT OR Apache-2.0) AND Unicode-DFS-2016",
+                "(MIT OR Apache-2.0) AND Unicode-DFS-2016",
+            ),
+            (
+                "Apache-2.0 WITH LLVM-exception OR GPL-2.0+",
+                "Apache-2.0 WITH LLVM-exception OR GPL-2.0+",
+            ),
+            (
+                "MIT AND BSD-3-Clause OR ISC",
+                "(MIT AND BSD-3-Clause) OR ISC",
+            ),
+        ] {
+            assert_eq!(LicenseExpr::parse(input).unwrap().to_string(), output);
+        }
+
+        for input in ["", "MIT OR", "(MIT", "MIT AND AND ISC", "MIT WITH", "M!T"] {
+            assert!(LicenseExpr::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_policy_report() {
+        let policy = LicensePolicy::new()
+            .allow(["MIT", "Apache-2.0", "Unicode-DFS-2016"])
+            .deny(["GPL-3.0"])
+            .exception("ring", ["OpenSSL"]);
+        let report = policy.evaluate(vec![
+            licensed("a", Some("MIT OR GPL-3.0")),
+            licensed("b", Some("(MIT OR Apache-2.0) AND Unicode-DFS-2016")),
+            licensed("c", Some("GPL-3.0 AND MIT")),
+            licensed("ring", Some("MIT AND OpenSSL")),
+            licensed("d", None),
+            licensed("e", Some("MIT OR")),
+        ]);
+
+        let names =
+            |crates: &[LicensedCrate]| crates.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&report.compliant), vec!["a", "b", "ring"]);
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.violations[0].krate.name, "c");
+        assert_eq!(report.violations[0].rejected, vec!["GPL-3.0"]);
+        assert_eq!(names(&report.missing), vec!["d"]);
+        assert_eq!(report.unparseable[0].krate.name, "e");
+        assert!(!report.is_compliant());
+    }
+
+    #[test]
+    fn test_policy_exceptions_and_later_versions() {
+        let accepts = |policy: &LicensePolicy, license: &str| {
+            let expr = LicenseExpr::parse(license).unwrap();
+            expr.evaluate(&|req| policy.accepts("demo", req))
+        };
+
+        let policy = LicensePolicy::new().allow(["Apache-2.0", "GPL-3.0"]);
+        assert!(accepts(&policy, "Apache-2.0 WITH LLVM-exception"));
+        assert!(accepts(&policy, "GPL-2.0+"));
+        assert!(accepts(&policy, "GPL-2.0-or-later"));
+        assert!(!accepts(&policy, "GPL-2.0"));
+        assert!(!accepts(&policy, "LGPL-2.1+"));
+
+        let policy = LicensePolicy::new()
+            .allow(["GPL-2.0 WITH Classpath-exception-2.0", "GPL-3.0-only"])
+            .deny(["Apache-2.0 WITH LLVM-exception"]);
+        assert!(accepts(&policy, "GPL-2.0 WITH Classpath-exception-2.0"));
+        assert!(!accepts(&policy, "GPL-2.0"));
+        assert!(accepts(&policy, "GPL-2.0+"));
+        assert!(accepts(&policy, "GPL-3.0+ WITH GCC-exception-3.1"));
+        assert!(!accepts(&policy, "Apache-2.0 WITH LLVM-exception"));
+
+        let policy = LicensePolicy::new().deny(["Apache-2.0 WITH LLVM-exception", "GPL-2.0"]);
+        assert!(accepts(&policy, "Apache-2.0"));
+        assert!(!accepts(&policy, "Apache-2.0 WITH LLVM-exception"));
+        assert!(!accepts(&policy, "GPL-2.0 WITH Classpath-exception-2.0"));
+    }
+}
@@ -0,0 +1,697 @@
+//! Auditing of `Cargo.lock` and `Cargo.toml` dependencies.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use futures::prelude::*;
+use semver::VersionReq;
+use serde_derive::*;
+
+use crate::{
+    error::Error,
+    types::{DependencyKind, Version},
+    AsyncClient, SyncClient,
+};
+
+/// Number of crates looked up concurrently by the async audit.
+const MAX_IN_FLIGHT: usize = 4;
+
+/// Error returned when a lockfile or manifest can not be parsed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ManifestError {
+    /// The file is not valid TOML or does not have the expected structure.
+    Toml(toml::de::Error),
+    /// The lockfile version is not supported.
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Toml(err) => write!(f, "Invalid manifest: {err}"),
+            ManifestError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported lockfile version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifestError::Toml(err) => Some(err),
+            ManifestError::UnsupportedVersion(_) => None,
+        }
+    }
+}
+
+impl From<toml::de::Error> for ManifestError {
+    fn from(err: toml::de::Error) -> Self {
+        ManifestError::Toml(err)
+    }
+}
+
+/// Returns `true` if the source refers to the crates.io index.
+fn is_crates_io(source: &str) -> bool {
+    source == "registry+https://github.com/rust-lang/crates.io-index"
+        || source == "sparse+https://index.crates.io/"
+}
+
+/// A package entry of a [`Lockfile`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    /// The name of the package.
+    pub name: String,
+    /// The locked version.
+    pub version: String,
+    /// Where the package comes from. Not set for local packages.
+    pub source: Option<String>,
+    /// The checksum of registry packages.
+    pub checksum: Option<String>,
+}
+
+impl LockedPackage {
+    /// Returns `true` if the package comes from crates.io.
+    pub fn is_crates_io(&self) -> bool {
+        self.source.as_deref().is_some_and(is_crates_io)
+    }
+}
+
+/// A parsed `Cargo.lock` file.
+///
+/// All lockfile versions written by Cargo (1 to 4) are supported.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lockfile {
+    /// The format version.
+    pub version: u32,
+    /// All packages, including local and git packages.
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct RawLockfile {
+    version: Option<u32>,
+    #[serde(default)]
+    package: Vec<RawPackage>,
+    metadata: Option<BTreeMap<String, toml::Value>>,
+}
+
+#[derive(Deserialize)]
+struct RawPackage {
+    #[serde(flatten)]
+    package: LockedPackage,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// Returns `true` if the dependencies are listed in the version 1 format.
+///
+/// Version 1 lists every dependency as `name version (source)`, with the
+/// source omitted for local packages. Later versions leave out the version
+/// and source unless they are needed to tell packages apart.
+fn has_v1_dependencies(packages: &[RawPackage]) -> bool {
+    let mut dependencies = packages.iter().flat_map(|p| &p.dependencies);
+    dependencies.clone().any(|dep| dep.ends_with(')')) && dependencies.all(|dep| dep.contains(' '))
+}
+
+impl Lockfile {
+    /// Parse the contents of a `Cargo.lock` file.
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        let raw: RawLockfile = toml::from_str(content)?;
+
+        // Versions 1 and 2 have no version key. Version 1 keeps the checksums
+        // in a separate table, which is missing if there are no registry
+        // packages, so it is also told apart by its dependency format.
+        let version = match raw.version {
+            Some(version @ 1..=4) => version,
+            Some(version) => return Err(ManifestError::UnsupportedVersion(version)),
+            None if raw.metadata.is_some() || has_v1_dependencies(&raw.package) => 1,
+            None => 2,
+        };
+
+        let mut packages = raw
+            .package
+            .into_iter()
+            .map(|raw| raw.package)
+            .collect::<Vec<_>>();
+        if let (1, Some(metadata)) = (version, &raw.metadata) {
+            for package in &mut packages {
+                let source = package.source.as_deref().unwrap_or_default();
+                let key = format!("checksum {} {} ({})", package.name, package.version, source);
+                if let Some(toml::Value::String(checksum)) = metadata.get(&key) {
+                    package.checksum = Some(checksum.clone());
+                }
+            }
+        }
+
+        Ok(Self { version, packages })
+    }
+
+    /// Audit all crates.io packages with a [`SyncClient`].
+    pub fn audit(&self, client: &SyncClient) -> Result<AuditReport, Error> {
+        let versions = fetch_versions(client, self.crate_names())?;
+        Ok(self.report(&versions))
+    }
+
+    /// Audit all crates.io packages with an [`AsyncClient`].
+    pub async fn audit_async(&self, client: &AsyncClient) -> Result<AuditReport, Error> {
+        let versions = fetch_versions_async(client, self.crate_names()).await?;
+        Ok(self.report(&versions))
+    }
+
+    fn crate_names(&self) -> BTreeSet<&str> {
+        self.packages
+            .iter()
+            .filter(|p| p.is_crates_io())
+            .map(|p| p.name.as_str())
+            .collect()
+    }
+
+    fn report(&self, versions: &HashMap<String, Option<Vec<Version>>>) -> AuditReport {
+        let mut report = AuditReport::default();
+        for package in &self.packages {
+            if package.is_crates_io() {
+                let versions = versions.get(&package.name).and_then(Option::as_deref);
+                report.packages.push(PackageAudit::new(
+                    &package.name,
+                    Some(&package.version),
+                    None,
+                    versions,
+                ));
+            } else {
+                report.skipped.push(package.name.clone());
+            }
+        }
+        report
+    }
+}
+
+impl std::str::FromStr for Lockfile {
+    type Err = ManifestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// A dependency declared in a [`Manifest`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestDependency {
+    /// The name of the package, after resolving renames.
+    pub name: String,
+    /// The version requirement, if any.
+    pub req: Option<String>,
+    /// The kind of the dependency.
+    pub kind: DependencyKind,
+    /// The target the dependency is limited to.
+    pub target: Option<String>,
+    /// Whether the dependency comes from crates.io.
+    pub crates_io: bool,
+}
+
+/// The dependencies of a parsed `Cargo.toml` file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    /// All declared dependencies, including path and git dependencies.
+    pub dependencies: Vec<ManifestDependency>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+struct RawDependencies {
+    #[serde(default)]
+    dependencies: BTreeMap<String, RawDependency>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, RawDependency>,
+    #[serde(default)]
+    build_dependencies: BTreeMap<String, RawDependency>,
+}
+
+#[derive(Deserialize)]
+struct RawManifest {
+    #[serde(flatten)]
+    dependencies: RawDependencies,
+    #[serde(default)]
+    target: BTreeMap<String, RawDependencies>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDependency {
+    Simple(String),
+    Detailed {
+        version: Option<String>,
+        package: Option<String>,
+        path: Option<String>,
+        git: Option<String>,
+        registry: Option<String>,
+        #[serde(default)]
+        workspace: bool,
+    },
+}
+
+impl Manifest {
+    /// Parse the contents of a `Cargo.toml` file.
+    ///
+    /// Workspace-inherited dependencies have no version requirement and are
+    /// not treated as crates.io dependencies.
+    pub fn parse(content: &str) -> Result<Self, ManifestError> {
+        let raw: RawManifest = toml::from_str(content)?;
+        let mut dependencies = Vec::new();
+        let targets = std::iter::once((None, raw.dependencies))
+            .chain(raw.target.into_iter().map(|(t, deps)| (Some(t), deps)));
+        for (target, deps) in targets {
+            for (kind, deps) in [
+                (DependencyKind::Normal, deps.dependencies),
+                (DependencyKind::Dev, deps.dev_dependencies),
+                (DependencyKind::Build, deps.build_dependencies),
+            ] {
+                for (name, dep) in deps {
+                    dependencies.push(ManifestDependency::new(
+                        name,
+                        dep,
+                        kind.clone(),
+                        target.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(Self { dependencies })
+    }
+
+    /// Audit all crates.io dependencies with a [`SyncClient`].
+    pub fn audit(&self, client: &SyncClient) -> Result<AuditReport, Error> {
+        let versions = fetch_versions(client, self.crate_names())?;
+        Ok(self.report(&versions))
+    }
+
+    /// Audit all crates.io dependencies with an [`AsyncClient`].
+    pub async fn audit_async(&self, client: &AsyncClient) -> Result<AuditReport, Error> {
+        let versions = fetch_versions_async(client, self.crate_names()).await?;
+        Ok(self.report(&versions))
+    }
+
+    fn crate_names(&self) -> BTreeSet<&str> {
+        self.dependencies
+            .iter()
+            .filter(|d| d.crates_io)
+            .map(|d| d.name.as_str())
+            .collect()
+    }
+
+    fn report(&self, versions: &HashMap<String, Option<Vec<Version>>>) -> AuditReport {
+        let mut report = AuditReport::default();
+        for dep in &self.dependencies {
+            if dep.crates_io {
+                let versions = versions.get(&dep.name).and_then(Option::as_deref);
+                report.packages.push(PackageAudit::new(
+                    &dep.name,
+                    None,
+                    dep.req.as_deref(),
+                    versions,
+                ));
+            } else {
+                report.skipped.push(dep.name.clone());
+            }
+        }
+        report
+    }
+}
+
+impl ManifestDependency {
+    fn new(name: String, dep: RawDependency, kind: DependencyKind, target: Option<String>) -> Self {
+        match dep {
+            RawDependency::Simple(req) => Self {
+                name,
+                req: Some(req),
+                kind,
+                target,
+                crates_io: true,
+            },
+            RawDependency::Detailed {
+                version,
+                package,
+                path,
+                git,
+                registry,
+                workspace,
+            } => Self {
+                name: package.unwrap_or(name),
+                crates_io: version.is_some()
+                    && path.is_none()
+                    && git.is_none()
+                    && registry.is_none()
+                    && !workspace,
+                req: version,
+                kind,
+                target,
+            },
+        }
+    }
+}
+
+/// The audit result of a single package.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackageAudit {
+    /// The name of the package.
+    pub name: String,
+    /// The locked version, when auditing a lockfile.
+    pub locked: Option<String>,
+    /// The version requirement, when auditing a manifest.
+    pub req: Option<String>,
+    /// Whether the crate exists on crates.io.
+    pub found: bool,
+    /// Whether the locked version is yanked.
+    pub yanked: Option<bool>,
+    /// The newest non-yanked version that is semver compatible with the
+    /// locked version or matches the requirement.
+    pub newest_compatible: Option<String>,
+    /// The newest non-yanked version, preferring stable versions.
+    pub newest: Option<String>,
+    /// The license of the locked version, or of the newest compatible one.
+    pub license: Option<String>,
+    /// The minimum supported Rust version of the locked version, or of the
+    /// newest compatible one.
+    pub rust_version: Option<String>,
+}
+
+impl PackageAudit {
+    fn new(
+        name: &str,
+        locked: Option<&str>,
+        req: Option<&str>,
+        versions: Option<&[Version]>,
+    ) -> Self {
+        let mut audit = Self {
+            name: name.to_string(),
+            locked: locked.map(String::from),
+            req: req.map(String::from),
+            found: versions.is_some(),
+            yanked: None,
+            newest_compatible: None,
+            newest: None,
+            license: None,
+            rust_version: None,
+        };
+        let versions = match versions {
+            Some(versions) => versions,
+            None => return audit,
+        };
+
+        let locked_version = locked.and_then(|num| versions.iter().find(|v| v.num == num));
+        let compatible_req = match (locked, req) {
+            (Some(num), _) => caret(num),
+            (None, Some(req)) => VersionReq::parse(req).ok(),
+            (None, None) => None,
+        };
+        let newest_compatible = compatible_req.and_then(|req| newest(versions, &req));
+        let newest =
+            newest(versions, &VersionReq::STAR).or_else(|| newest_with(versions, |_| true));
+
+        audit.yanked = locked_version.map(|v| v.yanked);
+        if let Some(v) = locked_version.or(newest_compatible) {
+            audit.license = v.license.clone();
+            audit.rust_version = v.rust_version.clone();
+        }
+        audit.newest_compatible = newest_compatible.map(|v| v.num.clone());
+        audit.newest = newest.map(|v| v.num.clone());
+        audit
+    }
+
+    /// Returns `true` if a newer compatible version than the locked one is
+    /// available.
+    pub fn has_compatible_update(&self) -> bool {
+        match (
+            parse_version(&self.locked),
+            parse_version(&self.newest_compatible),
+        ) {
+            (Some(locked), Some(newest)) => newest > locked,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the newest version is newer than the locked version
+    /// and not compatible with it or the requirement.
+    pub fn is_outdated(&self) -> bool {
+        let Some(newest) = parse_version(&self.newest) else {
+            return false;
+        };
+        parse_version(&self.locked).is_none_or(|locked| newest > locked)
+            && parse_version(&self.newest_compatible).is_none_or(|compatible| newest > compatible)
+    }
+}
+
+/// The caret requirement on a version, like `^1.2.3` for `1.2.3+build`.
+///
+/// Built from its parts, as requirements can not contain build metadata.
+fn caret(num: &str) -> Option<VersionReq> {
+    let version = semver::Version::parse(num).ok()?;
+    Some(VersionReq {
+        comparators: vec![semver::Comparator {
+            op: semver::Op::Caret,
+            major: version.major,
+            minor: Some(version.minor),
+            patch: Some(version.patch),
+            pre: version.pre,
+        }],
+    })
+}
+
+fn parse_version(num: &Option<String>) -> Option<semver::Version> {
+    num.as_deref()
+        .and_then(|num| semver::Version::parse(num).ok())
+}
+
+fn newest<'v>(versions: &'v [Version], req: &VersionReq) -> Option<&'v Version> {
+    newest_with(versions, |v| req.matches(v))
+}
+
+fn newest_with(
+    versions: &[Version],
+    matches: impl Fn(&semver::Version) -> bool,
+) -> Option<&Version> {
+    versions
+        .iter()
+        .filter(|v| !v.yanked)
+        .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+        .filter(|(semver, _)| matches(semver))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, v)| v)
+}
+
+/// The result of auditing a [`Lockfile`] or [`Manifest`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AuditReport {
+    /// The crates.io packages, in the order of the audited file.
+    pub packages: Vec<PackageAudit>,
+    /// Names of packages that do not come from crates.io.
+    pub skipped: Vec<String>,
+}
+
+impl AuditReport {
+    /// Packages whose locked version is yanked.
+    pub fn yanked(&self) -> impl Iterator<Item = &PackageAudit> {
+        self.packages.iter().filter(|p| p.yanked == Some(true))
+    }
+
+    /// Packages that do not exist on crates.io.
+    pub fn missing(&self) -> impl Iterator<Item = &PackageAudit> {
+        self.packages.iter().filter(|p| !p.found)
+    }
+}
+
+/// Load the versions of each crate once. Crates that do not exist map to
+/// `None`.
+fn fetch_versions(
+    client: &SyncClient,
+    names: BTreeSet<&str>,
+) -> Result<HashMap<String, Option<Vec<Version>>>, Error> {
+    names
+        .into_iter()
+        .map(|name| match client.crate_versions(name) {
+            Ok(versions) => Ok((name.to_string(), Some(versions))),
+            Err(Error::NotFound(_)) => Ok((name.to_string(), None)),
+            Err(err) => Err(err),
+        })
+        .collect()
+}
+
+async fn fetch_versions_async(
+    client: &AsyncClient,
+    names: BTreeSet<&str>,
+) -> Result<HashMap<String, Option<Vec<Version>>>, Error> {
+    stream::iter(names)
+        .map(|name| async move {
+            match client.crate_versions(name).await {
+                Ok(versions) => Ok((name.to_string(), Some(versions))),
+                Err(Error::NotFound(_)) => Ok((name.to_string(), None)),
+                Err(err) => Err(err),
+            }
+        })
+        .buffer_unordered(MAX_IN_FLIGHT)
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixture(name: &str) -> &'static str {
+        match name {
+            "v1" => include_str!("../tests/fixtures/lockfile-v1.lock"),
+            "v2" => include_str!("../tests/fixtures/lockfile-v2.lock"),
+            "v3" => include_str!("../tests/fixtures/lockfile-v3.lock"),
+            "v4" => include_str!("../tests/fixtures/lockfile-v4.lock"),
+            "manifest" => include_str!("../tests/fixtures/manifest.toml"),
+            _ => unreachable!(),
+        }
+    }
+
+    fn version(num: &str, yanked: bool, rust_version: Option<&str>) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "crate": "serde",
+            "created_at": "2020-01-01T00:00:00Z",
+            "updated_at": "2020-01-01T00:00:00Z",
+            "dl_path": "",
+            "downloads": 0,
+            "features": {},
+            "id": 1,
+            "num": num,
+            "yanked": yanked,
+            "license": "MIT OR Apache-2.0",
+            "readme_path": null,
+            "links": {"dependencies": "", "version_downloads": ""},
+            "crate_size": null,
+            "published_by": null,
+            "rust_version": rust_version,
+            "checksum": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_lockfile_versions() {
+        for (name, version) in [("v1", 1), ("v2", 2), ("v3", 3), ("v4", 4)] {
+            let lockfile = Lockfile::parse(fixture(name)).unwrap();
+            assert_eq!(lockfile.version, version, "{}", name);
+
+            let serde = lockfile
+                .packages
+                .iter()
+                .find(|p| p.name == "serde" && p.version == "1.0.100")
+                .unwrap();
+            assert!(serde.is_crates_io(), "{}", name);
+            assert_eq!(
+                serde.checksum.as_deref(),
+                Some("f4473e8512c4b08356d51aa0e3800f2b1f7ae2edc91ee4f6c56c9ef9c0f46d2e"),
+                "{}",
+                name
+            );
+        }
+
+        let lockfile = Lockfile::parse(fixture("v4")).unwrap();
+        assert_eq!(
+            lockfile.crate_names().into_iter().collect::<Vec<_>>(),
+            vec!["log", "serde"]
+        );
+        // Version 1 without a metadata table.
+        let v1 = fixture("v1");
+        let v1 = &v1[..v1.find("[metadata]").unwrap()];
+        assert_eq!(Lockfile::parse(v1).unwrap().version, 1);
+
+        assert!(matches!(
+            Lockfile::parse("version = 9"),
+            Err(ManifestError::UnsupportedVersion(9))
+        ));
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(fixture("manifest")).unwrap();
+        let deps = manifest
+            .dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.req.as_deref(), d.crates_io))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deps,
+            vec![
+                ("helper", None, false),
+                ("serde_json", Some("1"), true),
+                ("local", None, false),
+                ("log", Some("0.4"), true),
+                ("serde", Some("1.0.100"), true),
+                ("criterion", Some("0.5"), true),
+                ("cc", None, false),
+                ("libc", Some("0.2"), true),
+            ]
+        );
+        let libc = manifest.dependencies.last().unwrap();
+        assert_eq!(libc.target.as_deref(), Some("cfg(unix)"));
+    }
+
+    #[test]
+    fn test_audit_report() {
+        let lockfile = Lockfile::parse(fixture("v4")).unwrap();
+        let mut versions = HashMap::new();
+        versions.insert(
+            "serde".to_string(),
+            Some(vec![
+                version("0.9.15", true, None),
+                version("1.0.100", false, None),
+                version("1.0.200", false, Some("1.31")),
+                version("2.0.0-alpha.1", false, Some("1.70")),
+            ]),
+        );
+        versions.insert("log".to_string(), None);
+
+        let report = lockfile.report(&versions);
+        assert_eq!(report.skipped, vec!["demo", "helper"]);
+        assert_eq!(
+            report.missing().map(|p| &p.name).collect::<Vec<_>>(),
+            vec!["log"]
+        );
+
+        let old = &report.packages[1];
+        assert_eq!(old.locked.as_deref(), Some("0.9.15"));
+        assert_eq!(old.yanked, Some(true));
+        assert_eq!(old.newest_compatible, None);
+        assert!(!old.has_compatible_update());
+        assert!(old.is_outdated());
+
+        let current = &report.packages[2];
+        assert_eq!(current.yanked, Some(false));
+        assert_eq!(current.newest_compatible.as_deref(), Some("1.0.200"));
+        assert_eq!(current.newest.as_deref(), Some("1.0.200"));
+        assert_eq!(current.license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert!(current.has_compatible_update());
+        assert!(!current.is_outdated());
+        assert_eq!(report.yanked().count(), 1);
+    }
+
+    #[test]
+    fn test_compatible_update_of_yanked_version() {
+        let versions = vec![
+            version("1.0.0", false, None),
+            version("1.0.1", false, None),
+            version("1.0.2", true, None),
+        ];
+        let audit = PackageAudit::new("demo", Some("1.0.2"), None, Some(versions.as_slice()));
+        assert_eq!(audit.yanked, Some(true));
+        assert_eq!(audit.newest_compatible, None);
+        assert!(!audit.has_compatible_update());
+        // The newest version is older than the yanked one.
+        assert_eq!(audit.newest.as_deref(), Some("1.0.1"));
+        assert!(!audit.is_outdated());
+
+        let audit = PackageAudit::new("demo", Some("1.0.0"), None, Some(versions.as_slice()));
+        assert!(audit.has_compatible_update());
+
+        let audit = PackageAudit::new("demo", Some("1.0.0+build"), None, Some(versions.as_slice()));
+        assert_eq!(audit.newest_compatible.as_deref(), Some("1.0.1"));
+
+        let audit = PackageAudit::new("demo", None, Some("^1"), Some(versions.as_slice()));
+        assert!(!audit.has_compatible_update());
+    }
+}
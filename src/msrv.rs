@@ -0,0 +1,341 @@
+//! Minimum supported Rust version checks based on `Version::rust_version`.
+
+use semver::VersionReq;
+use serde_derive::*;
+
+use crate::types::{CrateResponse, Version};
+
+/// Parse a Rust version like `1.70` or `1.70.1`.
+fn parse_rust_version(version: &str) -> Result<semver::Version, semver::Error> {
+    let version = version.trim();
+    match version.split('.').count() {
+        1 => semver::Version::parse(&format!("{version}.0.0")),
+        2 => semver::Version::parse(&format!("{version}.0")),
+        _ => semver::Version::parse(version),
+    }
+}
+
+/// The requirement matching all versions semver-compatible with `version`,
+/// including older ones.
+fn compatible_series(version: &semver::Version) -> Option<VersionReq> {
+    let req = match (version.major, version.minor) {
+        (0, 0) => format!("=0.0.{}", version.patch),
+        (0, minor) => format!("^0.{minor}"),
+        (major, _) => format!("^{major}"),
+    };
+    VersionReq::parse(&req).ok()
+}
+
+/// How a dependency relates to the target toolchain of a [`MsrvCheck`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MsrvStatus {
+    /// The current version supports the toolchain.
+    Compatible,
+    /// The current version does not declare a `rust_version`.
+    Unknown,
+    /// The current version requires a newer toolchain, but a newer
+    /// semver-compatible version supports it.
+    Upgrade {
+        /// The newest semver-compatible version supporting the toolchain.
+        to: String,
+    },
+    /// The current version requires a newer toolchain, but an older
+    /// semver-compatible version supports it.
+    Downgrade {
+        /// The newest semver-compatible version supporting the toolchain.
+        to: String,
+    },
+    /// No semver-compatible version supports the toolchain.
+    Incompatible,
+}
+
+/// The result for a single dependency of a [`MsrvReport`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MsrvEntry {
+    /// The name of the crate.
+    pub name: String,
+    /// The currently used version.
+    pub version: String,
+    /// The `rust_version` of the currently used version.
+    pub rust_version: Option<String>,
+    /// The status of the current version.
+    #[serde(flatten)]
+    pub status: MsrvStatus,
+    /// The newest version of the crate supporting the toolchain, which may
+    /// be a semver-incompatible upgrade.
+    pub newest_supported: Option<String>,
+}
+
+/// The result of [`MsrvCheck::check`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MsrvReport {
+    /// The target toolchain.
+    pub toolchain: String,
+    /// The checked dependencies.
+    pub entries: Vec<MsrvEntry>,
+}
+
+impl MsrvReport {
+    /// Returns `true` if all dependencies support the toolchain without
+    /// changes.
+    pub fn is_compatible(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| matches!(e.status, MsrvStatus::Compatible | MsrvStatus::Unknown))
+    }
+
+    /// Dependencies that have to be upgraded.
+    pub fn upgrades(&self) -> impl Iterator<Item = &MsrvEntry> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.status, MsrvStatus::Upgrade { .. }))
+    }
+
+    /// Dependencies that have to be downgraded.
+    pub fn downgrades(&self) -> impl Iterator<Item = &MsrvEntry> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.status, MsrvStatus::Downgrade { .. }))
+    }
+
+    /// Dependencies without any compatible version supporting the toolchain.
+    pub fn incompatible(&self) -> impl Iterator<Item = &MsrvEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.status == MsrvStatus::Incompatible)
+    }
+}
+
+/// Checks crate versions against a target Rust toolchain.
+///
+/// Works on already fetched [`CrateResponse`] data. Versions that do not
+/// declare a `rust_version` are assumed to support every toolchain.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{MsrvCheck, SyncClient};
+///
+/// let client = SyncClient::new("my_bot (help@my_bot.com)", std::time::Duration::from_secs(1))?;
+/// let check = MsrvCheck::new("1.70")?;
+/// let krate = client.get_crate("serde")?;
+/// let report = check.check(vec![(&krate, "1.0.200")]);
+/// for entry in report.downgrades() {
+///     println!("{} has to be downgraded: {:?}", entry.name, entry.status);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MsrvCheck {
+    toolchain: semver::Version,
+}
+
+impl MsrvCheck {
+    /// Check against the given toolchain, like `1.70` or `1.70.1`.
+    pub fn new(toolchain: &str) -> Result<Self, semver::Error> {
+        Ok(Self {
+            toolchain: parse_rust_version(toolchain)?,
+        })
+    }
+
+    /// Whether a version supports the toolchain.
+    ///
+    /// Returns `None` if the version does not declare a valid `rust_version`.
+    pub fn supports(&self, version: &Version) -> Option<bool> {
+        let rust_version = parse_rust_version(version.rust_version.as_deref()?).ok()?;
+        Some(rust_version <= self.toolchain)
+    }
+
+    /// The newest non-yanked stable version supporting the toolchain.
+    pub fn newest_supported<'a>(&self, krate: &'a CrateResponse) -> Option<&'a Version> {
+        self.newest_matching(krate, &VersionReq::STAR)
+    }
+
+    /// The newest non-yanked version matching the requirement and supporting
+    /// the toolchain.
+    pub fn newest_matching<'a>(
+        &self,
+        krate: &'a CrateResponse,
+        req: &VersionReq,
+    ) -> Option<&'a Version> {
+        krate
+            .versions
+            .iter()
+            .filter(|v| !v.yanked && self.supports(v) != Some(false))
+            .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+            .filter(|(semver, _)| req.matches(semver))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v)
+    }
+
+    /// Check the currently used version of a crate.
+    ///
+    /// If the current version does not support the toolchain, the newest
+    /// semver-compatible version that does is suggested as an upgrade or
+    /// downgrade.
+    pub fn check_crate(&self, krate: &CrateResponse, version: &str) -> MsrvEntry {
+        let current = krate.versions.iter().find(|v| v.num == version);
+        let status = match current.and_then(|v| self.supports(v)) {
+            Some(true) => MsrvStatus::Compatible,
+            None => MsrvStatus::Unknown,
+            Some(false) => self.suggest(krate, version),
+        };
+
+        MsrvEntry {
+            name: krate.crate_data.name.clone(),
+            version: version.to_string(),
+            rust_version: current.and_then(|v| v.rust_version.clone()),
+            status,
+            newest_supported: self.newest_supported(krate).map(|v| v.num.clone()),
+        }
+    }
+
+    /// The newest semver-compatible version supporting the toolchain, for a
+    /// version that does not.
+    fn suggest(&self, krate: &CrateResponse, version: &str) -> MsrvStatus {
+        let Ok(current) = semver::Version::parse(version) else {
+            return MsrvStatus::Incompatible;
+        };
+        let newest = compatible_series(&current)
+            .and_then(|req| self.newest_matching(krate, &req))
+            .and_then(|v| v.semver().ok().map(|semver| (semver, v)));
+        match newest {
+            Some((semver, v)) if semver > current => MsrvStatus::Upgrade { to: v.num.clone() },
+            Some((_, v)) => MsrvStatus::Downgrade { to: v.num.clone() },
+            None => MsrvStatus::Incompatible,
+        }
+    }
+
+    /// Check a set of crates and their currently used versions.
+    pub fn check<'a, I>(&self, crates: I) -> MsrvReport
+    where
+        I: IntoIterator<Item = (&'a CrateResponse, &'a str)>,
+    {
+        MsrvReport {
+            toolchain: self.toolchain.to_string(),
+            entries: crates
+                .into_iter()
+                .map(|(krate, version)| self.check_crate(krate, version))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn krate(name: &str, versions: &[(&str, Option<&str>)]) -> CrateResponse {
+        let versions = versions
+            .iter()
+            .map(|(num, rust_version)| {
+                serde_json::json!({
+                    "crate": name,
+                    "created_at": "2020-01-01T00:00:00Z",
+                    "updated_at": "2020-01-01T00:00:00Z",
+                    "dl_path": "",
+                    "downloads": 0,
+                    "features": {},
+                    "id": 1,
+                    "num": num,
+                    "yanked": false,
+                    "license": null,
+                    "readme_path": null,
+                    "links": {"dependencies": "", "version_downloads": ""},
+                    "crate_size": null,
+                    "published_by": null,
+                    "rust_version": rust_version,
+                    "checksum": "",
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "categories": [],
+            "crate": {
+                "id": name,
+                "name": name,
+                "description": null,
+                "license": null,
+                "documentation": null,
+                "homepage": null,
+                "repository": null,
+                "downloads": 0,
+                "recent_downloads": null,
+                "categories": null,
+                "keywords": null,
+                "versions": null,
+                "max_version": "0.0.0",
+                "max_stable_version": null,
+                "links": {
+                    "owner_team": "",
+                    "owner_user": "",
+                    "owners": "",
+                    "reverse_dependencies": "",
+                    "version_downloads": "",
+                    "versions": null,
+                },
+                "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-01T00:00:00Z",
+                "exact_match": null,
+            },
+            "keywords": [],
+            "versions": versions,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_msrv_report() {
+        let a = krate(
+            "a",
+            &[
+                ("1.0.0", Some("1.56")),
+                ("1.1.0", Some("1.60")),
+                ("1.2.0", Some("1.74")),
+                ("2.0.0", Some("1.65")),
+            ],
+        );
+        let b = krate("b", &[("0.3.0", Some("1.70.1")), ("0.2.0", Some("1.50"))]);
+        let c = krate("c", &[("0.1.0", None)]);
+        let d = krate(
+            "d",
+            &[
+                ("0.4.0", Some("1.56")),
+                ("0.4.1", Some("1.80")),
+                ("0.4.2", Some("1.60")),
+            ],
+        );
+
+        let check = MsrvCheck::new("1.70").unwrap();
+        let report = check.check(vec![
+            (&a, "1.2.0"),
+            (&b, "0.3.0"),
+            (&c, "0.1.0"),
+            (&d, "0.4.1"),
+        ]);
+
+        assert_eq!(
+            report.entries[0].status,
+            MsrvStatus::Downgrade { to: "1.1.0".into() }
+        );
+        assert_eq!(report.entries[0].newest_supported.as_deref(), Some("2.0.0"));
+        assert_eq!(report.entries[1].status, MsrvStatus::Incompatible);
+        assert_eq!(report.entries[1].newest_supported.as_deref(), Some("0.2.0"));
+        assert_eq!(report.entries[2].status, MsrvStatus::Unknown);
+        assert_eq!(
+            report.entries[3].status,
+            MsrvStatus::Upgrade { to: "0.4.2".into() }
+        );
+        assert_eq!(report.entries[3].newest_supported.as_deref(), Some("0.4.2"));
+        assert!(!report.is_compatible());
+        assert_eq!(report.downgrades().count(), 1);
+        assert_eq!(report.upgrades().count(), 1);
+        assert_eq!(report.incompatible().count(), 1);
+
+        let check = MsrvCheck::new("1.74.0").unwrap();
+        assert!(check
+            .check(vec![(&a, "1.2.0"), (&b, "0.3.0")])
+            .is_compatible());
+    }
+}
@@ -0,0 +1,557 @@
+//! Generic pagination over listing endpoints.
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use serde_derive::*;
+
+use crate::Error;
+
+/// Position of a page in a paginated listing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PageCursor {
+    /// The first page, for listings that can not be addressed by page number.
+    First,
+    /// Page number, starting at 1.
+    Page(u64),
+    /// Opaque seek cursor, as returned by the API in `meta.next_page`.
+    Seek(String),
+}
+
+impl PageCursor {
+    /// Extract the cursor from the query string of a `meta.next_page` link,
+    /// like `?per_page=100&seek=WzEwMF0`.
+    pub fn from_query(query: &str) -> Option<Self> {
+        let pairs = url::form_urlencoded::parse(query.trim_start_matches('?').as_bytes());
+        let mut page = None;
+        for (key, value) in pairs {
+            match key.as_ref() {
+                "seek" => return Some(Self::Seek(value.into_owned())),
+                "page" => page = value.parse().ok().map(Self::Page),
+                _ => {}
+            }
+        }
+        page
+    }
+
+    pub(crate) fn append_to(&self, mut q: url::form_urlencoded::Serializer<'_, url::UrlQuery<'_>>) {
+        match self {
+            Self::First => {}
+            Self::Page(page) => {
+                q.append_pair("page", &page.to_string());
+            }
+            Self::Seek(seek) => {
+                q.append_pair("seek", seek);
+            }
+        }
+    }
+}
+
+/// A single page of a paginated listing.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// The items on this page.
+    pub items: Vec<T>,
+    /// The total number of items in the listing, if known.
+    pub total: Option<u64>,
+    /// Cursor of the following page, or `None` if this is the last page.
+    pub next: Option<PageCursor>,
+}
+
+impl<T> Page<T> {
+    /// Create a page of a listing paginated by page numbers.
+    ///
+    /// The following page is assumed to exist unless this page is empty.
+    pub fn numbered(items: Vec<T>, total: Option<u64>, page: u64) -> Self {
+        let next = if items.is_empty() {
+            None
+        } else {
+            Some(PageCursor::Page(page + 1))
+        };
+        Self { items, total, next }
+    }
+
+    /// Create a page from an API response, following `meta.next_page`.
+    ///
+    /// Falls back to the next page number if the API did not provide a link.
+    pub(crate) fn from_meta(items: Vec<T>, meta: &crate::types::Meta, cursor: &PageCursor) -> Self {
+        let next = match (meta.next_page.as_deref(), cursor) {
+            (Some(next), _) => PageCursor::from_query(next),
+            (None, PageCursor::Page(page)) if !items.is_empty() => Some(PageCursor::Page(page + 1)),
+            (None, _) => None,
+        };
+        Self {
+            items,
+            total: Some(meta.total),
+            next,
+        }
+    }
+}
+
+/// Serializable position in a paginated listing.
+///
+/// Obtained from [`Paginated::checkpoint`] or [`PaginatedIter::checkpoint`],
+/// and used to resume a listing where it left off with
+/// [`Paginated::resume`] or [`PaginatedIter::resume`].
+///
+/// Resuming listings paginated by page number can produce duplicates or miss
+/// items if the listing changed in the meantime. Seek cursors are stable.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PaginationCheckpoint {
+    /// Cursor of the page to continue with, or `None` if the listing is
+    /// exhausted.
+    pub cursor: Option<PageCursor>,
+    /// Number of items of that page that were already yielded.
+    pub skip: usize,
+    /// Number of items of the listing before the current position, including
+    /// items before the page the listing was started at.
+    ///
+    /// The listing ends once this reaches the total reported by the API.
+    pub yielded: u64,
+}
+
+impl PaginationCheckpoint {
+    /// A checkpoint at the start of a listing.
+    pub fn start(cursor: PageCursor) -> Self {
+        Self::start_at(cursor, 0)
+    }
+
+    /// A checkpoint at a cursor that is preceded by `offset` items, like
+    /// page 3 of a listing with 100 items per page.
+    pub fn start_at(cursor: PageCursor, offset: u64) -> Self {
+        Self {
+            cursor: Some(cursor),
+            skip: 0,
+            yielded: offset,
+        }
+    }
+}
+
+/// Bookkeeping shared by [`Paginated`] and [`PaginatedIter`].
+struct PageState<T> {
+    next: Option<PageCursor>,
+    /// Cursor of the page being fetched or most recently fetched.
+    current: Option<PageCursor>,
+    /// Whether the page at `current` has not been received yet.
+    awaiting: bool,
+    /// Number of items of the current page that were yielded.
+    consumed: usize,
+    /// Number of items to drop from the next page, when resuming.
+    skip: usize,
+    total: Option<u64>,
+    yielded: u64,
+    items: VecDeque<T>,
+}
+
+impl<T> PageState<T> {
+    fn new(checkpoint: PaginationCheckpoint) -> Self {
+        Self {
+            next: checkpoint.cursor,
+            current: None,
+            awaiting: false,
+            consumed: 0,
+            skip: checkpoint.skip,
+            total: None,
+            yielded: checkpoint.yielded,
+            items: VecDeque::new(),
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let item = self.items.pop_front()?;
+        self.consumed += 1;
+        self.yielded += 1;
+        Some(item)
+    }
+
+    fn take_next_cursor(&mut self) -> Option<PageCursor> {
+        if self.total.is_some_and(|total| self.yielded >= total) {
+            self.next = None;
+        }
+        let cursor = self.next.take()?;
+        self.current = Some(cursor.clone());
+        self.awaiting = true;
+        Some(cursor)
+    }
+
+    fn push_page(&mut self, page: Page<T>) {
+        if page.total.is_some() {
+            self.total = page.total;
+        }
+        self.next = page.next;
+        self.awaiting = false;
+        self.consumed = self.skip.min(page.items.len());
+        self.items.extend(page.items.into_iter().skip(self.skip));
+        self.skip = 0;
+    }
+
+    /// Stop after a failed fetch. The failed page is kept as the checkpoint.
+    fn fail(&mut self) {
+        self.next = None;
+        self.items.clear();
+    }
+
+    fn checkpoint(&self) -> PaginationCheckpoint {
+        let (cursor, skip) = if self.awaiting {
+            (self.current.clone(), self.skip)
+        } else if !self.items.is_empty() {
+            (self.current.clone(), self.consumed)
+        } else if self.total.is_some_and(|total| self.yielded >= total) {
+            (None, 0)
+        } else {
+            (self.next.clone(), 0)
+        };
+        PaginationCheckpoint {
+            cursor,
+            skip,
+            yielded: self.yielded,
+        }
+    }
+
+    fn page(&self) -> Option<u64> {
+        match self.current {
+            Some(PageCursor::Page(page)) => Some(page),
+            _ => None,
+        }
+    }
+}
+
+type FetchFuture<T> = BoxFuture<'static, Result<Page<T>, Error>>;
+
+/// Stream over all items of a paginated listing.
+///
+/// Pages are fetched lazily by the page-fetch function.
+/// The stream ends after the last page (see [`Page::next`]) or once the
+/// total number of items reported by the API has been yielded.
+/// After an error, the stream ends.
+pub struct Paginated<T> {
+    fetch: Box<dyn FnMut(PageCursor) -> FetchFuture<T> + Send>,
+    state: PageState<T>,
+    pending: Option<FetchFuture<T>>,
+}
+
+impl<T> Paginated<T> {
+    /// Create a stream starting at the given cursor.
+    pub fn new<F>(start: PageCursor, fetch: F) -> Self
+    where
+        F: FnMut(PageCursor) -> FetchFuture<T> + Send + 'static,
+    {
+        Self::resume(PaginationCheckpoint::start(start), fetch)
+    }
+
+    /// Create a stream continuing from a checkpoint.
+    pub fn resume<F>(checkpoint: PaginationCheckpoint, fetch: F) -> Self
+    where
+        F: FnMut(PageCursor) -> FetchFuture<T> + Send + 'static,
+    {
+        Self {
+            fetch: Box::new(fetch),
+            state: PageState::new(checkpoint),
+            pending: None,
+        }
+    }
+
+    /// The current position, for resuming the listing later.
+    ///
+    /// Items that have been fetched but not yet yielded are not part of the
+    /// checkpoint and will be fetched again on resume.
+    pub fn checkpoint(&self) -> PaginationCheckpoint {
+        self.state.checkpoint()
+    }
+
+    /// Cursor of the most recently fetched page, if any.
+    pub fn cursor(&self) -> Option<&PageCursor> {
+        self.state.current.as_ref()
+    }
+
+    /// Number of the most recently fetched page, if the listing is paginated
+    /// by page numbers.
+    pub fn page(&self) -> Option<u64> {
+        self.state.page()
+    }
+
+    /// Total number of items in the listing, known after the first page has
+    /// been fetched.
+    pub fn total(&self) -> Option<u64> {
+        self.state.total
+    }
+}
+
+impl<T: Unpin> futures::stream::Stream for Paginated<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let inner = self.get_mut();
+
+        loop {
+            if let Some(item) = inner.state.pop() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+
+            if let Some(fut) = &mut inner.pending {
+                let res = futures::ready!(fut.poll_unpin(cx));
+                inner.pending = None;
+                match res {
+                    Ok(page) => inner.state.push_page(page),
+                    Err(err) => {
+                        inner.state.fail();
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                }
+                continue;
+            }
+
+            match inner.state.take_next_cursor() {
+                Some(cursor) => inner.pending = Some((inner.fetch)(cursor)),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Blocking iterator over all items of a paginated listing.
+///
+/// The blocking equivalent of [`Paginated`].
+pub struct PaginatedIter<'a, T> {
+    fetch: Box<dyn FnMut(PageCursor) -> Result<Page<T>, Error> + 'a>,
+    state: PageState<T>,
+}
+
+impl<'a, T> PaginatedIter<'a, T> {
+    /// Create an iterator starting at the given cursor.
+    pub fn new<F>(start: PageCursor, fetch: F) -> Self
+    where
+        F: FnMut(PageCursor) -> Result<Page<T>, Error> + 'a,
+    {
+        Self::resume(PaginationCheckpoint::start(start), fetch)
+    }
+
+    /// Create an iterator continuing from a checkpoint.
+    pub fn resume<F>(checkpoint: PaginationCheckpoint, fetch: F) -> Self
+    where
+        F: FnMut(PageCursor) -> Result<Page<T>, Error> + 'a,
+    {
+        Self {
+            fetch: Box::new(fetch),
+            state: PageState::new(checkpoint),
+        }
+    }
+
+    /// The current position, for resuming the listing later.
+    pub fn checkpoint(&self) -> PaginationCheckpoint {
+        self.state.checkpoint()
+    }
+
+    /// Cursor of the most recently fetched page, if any.
+    pub fn cursor(&self) -> Option<&PageCursor> {
+        self.state.current.as_ref()
+    }
+
+    /// Number of the most recently fetched page, if the listing is paginated
+    /// by page numbers.
+    pub fn page(&self) -> Option<u64> {
+        self.state.page()
+    }
+
+    /// Total number of items in the listing, known after the first page has
+    /// been fetched.
+    pub fn total(&self) -> Option<u64> {
+        self.state.total
+    }
+}
+
+impl<T> Iterator for PaginatedIter<'_, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.state.pop() {
+                return Some(Ok(item));
+            }
+
+            let cursor = self.state.take_next_cursor()?;
+            match (self.fetch)(cursor) {
+                Ok(page) => self.state.push_page(page),
+                Err(err) => {
+                    self.state.fail();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::TryStreamExt;
+
+    fn fetch_page(cursor: PageCursor) -> Result<Page<u64>, Error> {
+        // Three pages of two items, the second one linking to the third via
+        // a seek cursor.
+        Ok(match cursor {
+            PageCursor::Page(1) => Page {
+                items: vec![1, 2],
+                total: Some(6),
+                next: Some(PageCursor::Page(2)),
+            },
+            PageCursor::Page(2) => Page {
+                items: vec![3, 4],
+                total: Some(6),
+                next: PageCursor::from_query("?per_page=2&seek=abc"),
+            },
+            PageCursor::Seek(seek) if seek == "abc" => Page {
+                items: vec![5, 6],
+                total: Some(6),
+                next: Some(PageCursor::Seek("never-fetched".to_string())),
+            },
+            other => panic!("unexpected cursor {:?}", other),
+        })
+    }
+
+    #[test]
+    fn test_paginated_iter() {
+        let mut iter = PaginatedIter::new(PageCursor::Page(1), fetch_page);
+        let items = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(items, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(iter.total(), Some(6));
+        assert_eq!(iter.cursor(), Some(&PageCursor::Seek("abc".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_paginated_stream() {
+        let stream = Paginated::new(PageCursor::Page(1), |cursor| {
+            futures::future::ready(fetch_page(cursor)).boxed()
+        });
+        let items = stream.try_collect::<Vec<_>>().await.unwrap();
+
+        assert_eq!(items, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_paginated_stops_on_empty_page() {
+        let iter = PaginatedIter::new(PageCursor::Page(1), |cursor| {
+            let PageCursor::Page(page) = cursor else {
+                unreachable!()
+            };
+            let items = if page < 3 { vec![page] } else { Vec::new() };
+            Ok(Page::numbered(items, None, page))
+        });
+
+        assert_eq!(iter.map(Result::unwrap).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_paginated_continues_after_empty_page_with_next() {
+        let iter = PaginatedIter::new(PageCursor::Page(1), |cursor| {
+            let PageCursor::Page(page) = cursor else {
+                unreachable!()
+            };
+            let items = if page == 2 { Vec::new() } else { vec![page] };
+            let next = (page < 3).then(|| PageCursor::Page(page + 1));
+            Ok(Page {
+                items,
+                total: None,
+                next,
+            })
+        });
+
+        assert_eq!(iter.map(Result::unwrap).collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_paginated_starting_past_first_page() {
+        let start = PaginationCheckpoint::start_at(PageCursor::Page(2), 2);
+        let iter = PaginatedIter::resume(start, |cursor| match cursor {
+            // The page links to the next page, but the total has been
+            // reached.
+            PageCursor::Page(2) => Ok(Page::numbered(vec![3, 4], Some(4), 2)),
+            other => panic!("unexpected cursor {:?}", other),
+        });
+
+        assert_eq!(iter.map(Result::unwrap).collect::<Vec<_>>(), vec![3, 4]);
+
+        let query = crate::CratesQuery::builder().page(3).page_size(50).build();
+        assert_eq!(query.start().yielded, 100);
+    }
+
+    #[test]
+    fn test_paginated_iter_resume() {
+        let mut iter = PaginatedIter::new(PageCursor::Page(1), fetch_page);
+        let first = iter
+            .by_ref()
+            .take(3)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(first, vec![1, 2, 3]);
+
+        let checkpoint = iter.checkpoint();
+        assert_eq!(
+            checkpoint,
+            PaginationCheckpoint {
+                cursor: Some(PageCursor::Page(2)),
+                skip: 1,
+                yielded: 3,
+            }
+        );
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let checkpoint = serde_json::from_str(&json).unwrap();
+        let rest = PaginatedIter::resume(checkpoint, fetch_page)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rest, vec![4, 5, 6]);
+
+        let mut iter = PaginatedIter::resume(iter.checkpoint(), fetch_page);
+        iter.by_ref().for_each(drop);
+        assert_eq!(iter.checkpoint().cursor, None);
+    }
+
+    #[test]
+    fn test_paginated_checkpoint_after_total() {
+        // The last page links to a further page, but all items were yielded.
+        let mut iter = PaginatedIter::new(PageCursor::Page(1), fetch_page);
+        let items = iter
+            .by_ref()
+            .take(6)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(items, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            iter.checkpoint(),
+            PaginationCheckpoint {
+                cursor: None,
+                skip: 0,
+                yielded: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_page_from_meta_follows_next_page() {
+        let meta: crate::Meta = serde_json::from_str(
+            r#"{"total": 150000, "next_page": "?per_page=100&seek=WzEwMF0", "prev_page": null}"#,
+        )
+        .unwrap();
+        let page = Page::from_meta(vec![1], &meta, &PageCursor::Page(1));
+        assert_eq!(page.next, Some(PageCursor::Seek("WzEwMF0".to_string())));
+
+        let mut query = crate::CratesQuery::builder().page(3).build();
+        query.set_cursor(&PageCursor::Seek("WzEwMF0".to_string()));
+        let mut url = url::Url::parse("https://crates.io/api/v1/crates").unwrap();
+        query.build(url.query_pairs_mut());
+        assert!(url.query().unwrap().starts_with("seek=WzEwMF0&"));
+
+        let meta = crate::Meta {
+            total: 2,
+            ..Default::default()
+        };
+        let page = Page::from_meta(vec![1, 2], &meta, &PageCursor::Page(1));
+        assert_eq!(page.next, Some(PageCursor::Page(2)));
+    }
+}
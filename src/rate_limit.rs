@@ -0,0 +1,241 @@
+//! Rate limiting of API requests.
+
+#[cfg(feature = "file-lock")]
+use std::{
+    io::{Read, Seek, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Decides when requests may be sent.
+///
+/// Rate limiters work with reservations: every request reserves a slot and
+/// then waits until that slot has arrived.
+/// This allows multiple requests to be in flight at the same time while
+/// still spacing out their start times.
+/// Reserved slots are not returned, so a request that is cancelled while
+/// waiting, like a dropped future of the [`AsyncClient`](crate::AsyncClient),
+/// still uses up its slot.
+///
+/// A single limiter can be shared between multiple clients, including an
+/// [`AsyncClient`](crate::AsyncClient) and a [`SyncClient`](crate::SyncClient),
+/// by passing the same `Arc` to `with_rate_limiter`.
+///
+/// ```rust
+/// # fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// use crates_io_api::{AsyncClient, SyncClient, TokenBucketLimiter};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let limiter = Arc::new(TokenBucketLimiter::new(5, Duration::from_secs(1)));
+/// let user_agent = "my_bot (help@my_bot.com)";
+/// let sync_client = SyncClient::new(user_agent, Duration::from_secs(1))?
+///     .with_rate_limiter(limiter.clone());
+/// let async_client = AsyncClient::new(user_agent, Duration::from_secs(1))?
+///     .with_rate_limiter(limiter);
+/// # Ok(())
+/// # }
+/// ```
+pub trait RateLimiter: Send + Sync {
+    /// Reserve a slot for a single request.
+    ///
+    /// Returns how long the caller has to wait before sending the request.
+    fn reserve(&self) -> Duration;
+}
+
+/// Allows one request per interval.
+///
+/// This is the limiter used by the client constructors.
+#[derive(Debug)]
+pub struct FixedIntervalLimiter {
+    interval: Duration,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl FixedIntervalLimiter {
+    /// Create a limiter allowing one request per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_slot: Mutex::new(None),
+        }
+    }
+}
+
+impl RateLimiter for FixedIntervalLimiter {
+    fn reserve(&self) -> Duration {
+        let now = Instant::now();
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = next_slot.map_or(now, |next| next.max(now));
+        *next_slot = Some(slot + self.interval);
+        slot - now
+    }
+}
+
+/// Allows bursts of up to `capacity` requests, refilling one request per
+/// interval.
+///
+/// On average this sends at most one request per interval, like
+/// [`FixedIntervalLimiter`], but short bursts (for example when fetching
+/// all versions of a crate) are sent without delay.
+#[derive(Debug)]
+pub struct TokenBucketLimiter {
+    capacity: f64,
+    interval: Duration,
+    state: Mutex<TokenBucketState>,
+}
+
+#[derive(Debug)]
+struct TokenBucketState {
+    /// Available tokens. Negative values are reservations that still have to
+    /// be refilled.
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucketLimiter {
+    /// Create a full bucket holding `capacity` tokens, refilling one token
+    /// per `interval`.
+    pub fn new(capacity: u32, interval: Duration) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        Self {
+            capacity,
+            interval,
+            state: Mutex::new(TokenBucketState {
+                tokens: capacity,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl RateLimiter for TokenBucketLimiter {
+    fn reserve(&self) -> Duration {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        if !self.interval.is_zero() {
+            let refilled =
+                now.duration_since(state.updated_at).as_secs_f64() / self.interval.as_secs_f64();
+            state.tokens = (state.tokens + refilled).min(self.capacity);
+        } else {
+            state.tokens = self.capacity;
+        }
+        state.updated_at = now;
+
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            self.interval.mul_f64(-state.tokens)
+        }
+    }
+}
+
+/// Allows one request per interval across all processes on a host that use
+/// the same lock file.
+///
+/// The time of the next free slot is stored in the file, which is locked
+/// while a slot is reserved.
+/// If the file can not be accessed, a full interval is waited.
+///
+/// Reserving a slot reads and writes the file and blocks until the lock is
+/// released by other processes, so this limiter must not be used with the
+/// [`AsyncClient`](crate::AsyncClient), where it would block the executor.
+///
+/// Requires the `file-lock` feature.
+#[cfg(feature = "file-lock")]
+#[derive(Debug)]
+pub struct FileLockLimiter {
+    path: PathBuf,
+    interval: Duration,
+}
+
+#[cfg(feature = "file-lock")]
+impl FileLockLimiter {
+    /// Create a limiter coordinating through the file at `path`.
+    ///
+    /// The file is created if it does not exist.
+    pub fn new(path: impl Into<PathBuf>, interval: Duration) -> Self {
+        Self {
+            path: path.into(),
+            interval,
+        }
+    }
+
+    fn try_reserve(&self) -> std::io::Result<Duration> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        fs2::FileExt::lock_exclusive(&file)?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let next_slot = content
+            .trim()
+            .parse::<u64>()
+            .map(Duration::from_nanos)
+            .unwrap_or_default();
+        let slot = next_slot.max(now);
+
+        let next = (slot + self.interval).as_nanos().to_string();
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(next.as_bytes())?;
+        fs2::FileExt::unlock(&file)?;
+
+        Ok(slot - now)
+    }
+}
+
+#[cfg(feature = "file-lock")]
+impl RateLimiter for FileLockLimiter {
+    fn reserve(&self) -> Duration {
+        self.try_reserve().unwrap_or(self.interval)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fixed_interval_spaces_requests() {
+        let limiter = FixedIntervalLimiter::new(Duration::from_secs(10));
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+        assert!(limiter.reserve() > Duration::from_secs(9));
+        assert!(limiter.reserve() > Duration::from_secs(19));
+    }
+
+    #[test]
+    fn test_token_bucket_allows_bursts() {
+        let limiter = TokenBucketLimiter::new(3, Duration::from_secs(10));
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(), Duration::ZERO);
+        }
+        assert!(limiter.reserve() > Duration::from_secs(9));
+    }
+
+    #[cfg(feature = "file-lock")]
+    #[test]
+    fn test_file_lock_limiter_shares_slots() {
+        let path =
+            std::env::temp_dir().join(format!("crates_io_api_rate_limit_{}", std::process::id()));
+        let a = FileLockLimiter::new(&path, Duration::from_secs(10));
+        let b = FileLockLimiter::new(&path, Duration::from_secs(10));
+
+        assert_eq!(a.reserve(), Duration::ZERO);
+        assert!(b.reserve() > Duration::from_secs(9));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
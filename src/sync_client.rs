@@ -1,17 +1,23 @@
 use super::*;
-use std::iter::Extend;
+use std::sync::Arc;
 
-use reqwest::{blocking::Client as HttpClient, header, StatusCode, Url};
+use reqwest::{blocking::Client as HttpClient, header, Url};
 use serde::de::DeserializeOwned;
 
-use crate::{error::JsonDecodeError, types::*};
+use crate::{
+    cache::{Endpoint, Lookup, ResponseCache},
+    compare::compared_version,
+    pagination::{Page, PageCursor, PaginatedIter},
+    rate_limit::{FixedIntervalLimiter, RateLimiter},
+    types::*,
+};
 
 /// A synchronous client for the crates.io API.
 pub struct SyncClient {
     client: HttpClient,
     base_url: Url,
-    rate_limit: std::time::Duration,
-    last_request_time: std::sync::Mutex<Option<std::time::Instant>>,
+    rate_limiter: Arc<dyn RateLimiter>,
+    cache: Option<ResponseCache>,
 }
 
 impl SyncClient {
@@ -22,8 +28,10 @@ impl SyncClient {
     /// To respect the offical [Crawler Policy](https://crates.io/policies#crawlers),
     /// you must specify both a descriptive user agent and a rate limit interval.
     ///
-    /// At most one request will be executed in the specified duration.
+    /// Every request reserves the next free slot of the rate limiter, so at
+    /// most one request will be started in the specified duration.
     /// The guidelines suggest 1 per second or less.
+    /// Use [`SyncClient::with_rate_limiter`] for other rate limiting strategies.
     ///
     /// Example user agent: `"my_bot (my_bot.com/info)"` or `"my_bot (help@my_bot.com)"`.
     ///
@@ -52,61 +60,60 @@ impl SyncClient {
                 .build()
                 .unwrap(),
             base_url: Url::parse("https://crates.io/api/v1/").unwrap(),
-            rate_limit,
-            last_request_time: std::sync::Mutex::new(None),
+            rate_limiter: Arc::new(FixedIntervalLimiter::new(rate_limit)),
+            cache: None,
         })
     }
 
-    fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T, Error> {
-        let mut lock = self.last_request_time.lock().unwrap();
-        if let Some(last_request_time) = lock.take() {
-            let now = std::time::Instant::now();
-            if last_request_time.elapsed() < self.rate_limit {
-                std::thread::sleep((last_request_time + self.rate_limit) - now);
-            }
-        }
+    /// Replace the rate limiter.
+    ///
+    /// The limiter can be shared with other clients, see [`RateLimiter`].
+    #[must_use]
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
 
-        let time = std::time::Instant::now();
+    /// Cache responses and revalidate them with conditional requests.
+    ///
+    /// See [`ResponseCache`] for details.
+    #[must_use]
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
 
-        let res = self.client.get(url.clone()).send()?;
+    fn get<T: DeserializeOwned>(&self, url: Url, endpoint: Endpoint) -> Result<T, Error> {
+        let cached = match &self.cache {
+            Some(cache) => match cache.lookup(&url, endpoint)? {
+                Lookup::Fresh(value) => return Ok(value),
+                Lookup::Send(cached) => cached,
+            },
+            None => None,
+        };
 
-        if !res.status().is_success() {
-            let err = match res.status() {
-                StatusCode::NOT_FOUND => Error::NotFound(super::error::NotFoundError {
-                    url: url.to_string(),
-                }),
-                StatusCode::FORBIDDEN => {
-                    let reason = res.text().unwrap_or_default();
-                    Error::PermissionDenied(super::error::PermissionDeniedError { reason })
-                }
-                _ => Error::from(res.error_for_status().unwrap_err()),
-            };
+        std::thread::sleep(self.rate_limiter.reserve());
 
-            return Err(err);
+        let mut req = self.client.get(url.clone());
+        if let Some(entry) = &cached {
+            req = req.headers(ResponseCache::conditional_headers(entry));
         }
+        let res = req.send()?;
 
-        *lock = Some(time);
-
+        let status = res.status();
+        let headers = res.headers().clone();
         let content = res.text()?;
 
-        // First, check for api errors.
-
-        if let Ok(errors) = serde_json::from_str::<ApiErrors>(&content) {
-            return Err(Error::Api(errors));
+        match &self.cache {
+            Some(cache) => cache.response(&url, cached, status, &headers, content),
+            None => super::async_client::parse_response(&url, status, &headers, &content),
         }
-
-        let jd = &mut serde_json::Deserializer::from_str(&content);
-        serde_path_to_error::deserialize::<_, T>(jd).map_err(|err| {
-            Error::JsonDecode(JsonDecodeError {
-                message: format!("Could not decode JSON: {err} (path: {})", err.path()),
-            })
-        })
     }
 
     /// Retrieve a summary containing crates.io wide information.
     pub fn summary(&self) -> Result<Summary, Error> {
         let url = self.base_url.join("summary").unwrap();
-        self.get(url)
+        self.get(url, Endpoint::Summary)
     }
 
     /// Retrieve information of a crate.
@@ -114,19 +121,49 @@ impl SyncClient {
     /// If you require detailed information, consider using [full_crate]().
     pub fn get_crate(&self, crate_name: &str) -> Result<CrateResponse, Error> {
         let url = super::async_client::build_crate_url(&self.base_url, crate_name)?;
-        self.get(url)
+        self.get(url, Endpoint::Crate)
     }
 
     /// Retrieve download stats for a crate.
     pub fn crate_downloads(&self, crate_name: &str) -> Result<CrateDownloads, Error> {
         let url = super::async_client::build_crate_downloads_url(&self.base_url, crate_name)?;
-        self.get(url)
+        self.get(url, Endpoint::CrateDownloads)
+    }
+
+    /// Compare crates side by side.
+    ///
+    /// Loads the crate, its owners, its reverse dependency count and the
+    /// dependencies of its latest stable version for each crate.
+    pub fn compare_crates(&self, names: &[&str]) -> Result<CrateComparison, Error> {
+        let now = crate::compare::now();
+        let mut crates = Vec::with_capacity(names.len());
+        for name in names {
+            let krate = self.get_crate(name)?;
+            let owners = self.crate_owners(name)?;
+            let reverse_dependencies = self.crate_reverse_dependency_count(name)?;
+            let dependencies = self.crate_dependencies(name, compared_version(&krate))?;
+            crates.push(ComparedCrate::new(
+                &krate,
+                &owners,
+                &dependencies,
+                reverse_dependencies,
+                now,
+            ));
+        }
+        Ok(CrateComparison { crates })
+    }
+
+    /// Retrieve download stats for a crate, joined with its versions.
+    pub fn crate_download_analytics(&self, crate_name: &str) -> Result<DownloadAnalytics, Error> {
+        let downloads = self.crate_downloads(crate_name)?;
+        let versions = self.crate_versions(crate_name)?;
+        Ok(DownloadAnalytics::new(&downloads, &versions))
     }
 
     /// Retrieve the owners of a crate.
     pub fn crate_owners(&self, crate_name: &str) -> Result<Vec<User>, Error> {
         let url = super::async_client::build_crate_owners_url(&self.base_url, crate_name)?;
-        let resp: Owners = self.get(url)?;
+        let resp: Owners = self.get(url, Endpoint::CrateOwners)?;
         Ok(resp.users)
     }
 
@@ -138,15 +175,47 @@ impl SyncClient {
         crate_name: &str,
         page: u64,
     ) -> Result<ReverseDependencies, Error> {
-        let url =
-            super::async_client::build_crate_reverse_deps_url(&self.base_url, crate_name, page)?;
-        let page = self.get::<ReverseDependenciesAsReceived>(url)?;
+        let mut query = ReverseDependenciesQuery::default();
+        query.set_page(page);
+        self.crate_reverse_dependencies_page_with_query(crate_name, &query)
+    }
+
+    /// Get the page of reverse dependencies selected by the query.
+    ///
+    /// The filters of the query only apply to this page, so a dependent can
+    /// appear with an older version if its latest version is on another
+    /// page. `meta.total` is the number of dependents reported by the API.
+    pub fn crate_reverse_dependencies_page_with_query(
+        &self,
+        crate_name: &str,
+        query: &ReverseDependenciesQuery,
+    ) -> Result<ReverseDependencies, Error> {
+        let mut res = self.fetch_reverse_dependencies(crate_name, query.page, query.per_page)?;
+        res.dependencies = query.filter_page(res.dependencies);
+        Ok(res)
+    }
+
+    fn fetch_reverse_dependencies(
+        &self,
+        crate_name: &str,
+        page: u64,
+        per_page: u64,
+    ) -> Result<ReverseDependencies, Error> {
+        let url = super::async_client::build_crate_reverse_deps_url(
+            &self.base_url,
+            crate_name,
+            page.max(1),
+            per_page,
+        )?;
+        let page = self.get::<ReverseDependenciesAsReceived>(url, Endpoint::ReverseDependencies)?;
 
         let mut deps = ReverseDependencies {
             dependencies: Vec::new(),
-            meta: Meta { total: 0 },
+            meta: Meta {
+                total: page.meta.total,
+                ..Meta::default()
+            },
         };
-        deps.meta.total = page.meta.total;
         deps.extend(page);
         Ok(deps)
     }
@@ -156,25 +225,57 @@ impl SyncClient {
     /// Note: Since the reverse dependency endpoint requires pagination, this
     /// will result in multiple requests if the crate has more than 100 reverse
     /// dependencies.
+    /// Consider [`SyncClient::crate_reverse_dependencies_iter`] for crates with
+    /// many dependents.
     pub fn crate_reverse_dependencies(
         &self,
         crate_name: &str,
     ) -> Result<ReverseDependencies, Error> {
-        let mut deps = ReverseDependencies {
-            dependencies: Vec::new(),
-            meta: Meta { total: 0 },
-        };
+        self.crate_reverse_dependencies_with_query(crate_name, ReverseDependenciesQuery::default())
+    }
 
-        for page_number in 1.. {
-            let page = self.crate_reverse_dependencies_page(crate_name, page_number)?;
-            if page.dependencies.is_empty() {
-                break;
-            }
+    /// Load all reverse dependencies of a crate matching the query.
+    ///
+    /// Loading starts at the page of the query. `meta.total` is the number of
+    /// dependents reported by the API, before any filters were applied.
+    pub fn crate_reverse_dependencies_with_query(
+        &self,
+        crate_name: &str,
+        query: ReverseDependenciesQuery,
+    ) -> Result<ReverseDependencies, Error> {
+        let mut pages = self.crate_reverse_dependencies_iter(crate_name, query);
+        let dependencies = pages.by_ref().collect::<Result<_, _>>()?;
+
+        Ok(ReverseDependencies {
+            dependencies,
+            meta: Meta {
+                total: pages.total().unwrap_or_default(),
+                ..Meta::default()
+            },
+        })
+    }
 
-            deps.dependencies.extend(page.dependencies);
-            deps.meta.total = page.meta.total;
-        }
-        Ok(deps)
+    /// Get an iterator over all reverse dependencies of a crate matching the
+    /// query.
+    ///
+    /// Pages are fetched lazily, so dependents are available as soon as the
+    /// first page has been received.
+    pub fn crate_reverse_dependencies_iter<'a>(
+        &'a self,
+        crate_name: &'a str,
+        query: ReverseDependenciesQuery,
+    ) -> PaginatedIter<'a, ReverseDependency> {
+        let start = query.start();
+        let per_page = query.per_page;
+        let mut filter = ReverseDependenciesFilter::new(query);
+        PaginatedIter::resume(start, move |cursor| {
+            let page = match cursor {
+                PageCursor::Page(page) => page,
+                _ => 1,
+            };
+            let res = self.fetch_reverse_dependencies(crate_name, page, per_page)?;
+            Ok(filter.page(page, res))
+        })
     }
 
     /// Get the total count of reverse dependencies for a given crate.
@@ -183,11 +284,56 @@ impl SyncClient {
         Ok(page.meta.total)
     }
 
+    /// Get a single page of versions of a crate.
+    ///
+    /// Use [`PageCursor::First`] for the first page, and the cursor in
+    /// `meta.next_page` for the following ones.
+    pub fn crate_versions_page(
+        &self,
+        crate_name: &str,
+        cursor: &PageCursor,
+    ) -> Result<VersionsPage, Error> {
+        let url =
+            super::async_client::build_crate_versions_url(&self.base_url, crate_name, cursor)?;
+        self.get(url, Endpoint::Versions)
+    }
+
+    /// Load all versions of a crate.
+    pub fn crate_versions(&self, crate_name: &str) -> Result<Vec<Version>, Error> {
+        self.crate_versions_iter(crate_name).collect()
+    }
+
+    /// Load the audit log of a crate.
+    ///
+    /// The audit actions of all versions, like publishes and yanks, are merged
+    /// into a single chronologically sorted timeline.
+    pub fn crate_audit_log(&self, crate_name: &str) -> Result<Vec<AuditLogEntry>, Error> {
+        let versions = self.crate_versions(crate_name)?;
+        Ok(audit_log(&versions))
+    }
+
+    /// Get an iterator over all versions of a crate.
+    pub fn crate_versions_iter<'a>(&'a self, crate_name: &'a str) -> PaginatedIter<'a, Version> {
+        PaginatedIter::new(PageCursor::First, move |cursor| {
+            let res = self.crate_versions_page(crate_name, &cursor)?;
+            let next = res
+                .meta
+                .next_page
+                .as_deref()
+                .and_then(PageCursor::from_query);
+            Ok(Page {
+                items: res.versions,
+                total: Some(res.meta.total),
+                next,
+            })
+        })
+    }
+
     /// Retrieve the authors for a crate version.
     pub fn crate_authors(&self, crate_name: &str, version: &str) -> Result<Authors, Error> {
         let url =
             super::async_client::build_crate_authors_url(&self.base_url, crate_name, version)?;
-        let res: AuthorsResponse = self.get(url)?;
+        let res: AuthorsResponse = self.get(url, Endpoint::Authors)?;
         Ok(Authors {
             names: res.meta.names,
         })
@@ -201,7 +347,7 @@ impl SyncClient {
     ) -> Result<Vec<Dependency>, Error> {
         let url =
             super::async_client::build_crate_dependencies_url(&self.base_url, crate_name, version)?;
-        let resp: Dependencies = self.get(url)?;
+        let resp: Dependencies = self.get(url, Endpoint::Dependencies)?;
         Ok(resp.dependencies)
     }
 
@@ -228,24 +374,20 @@ impl SyncClient {
         let owners = self.crate_owners(name)?;
         let reverse_dependencies = self.crate_reverse_dependencies(name)?;
 
-        let versions = if resp.versions.is_empty() {
-            vec![]
-        } else if all_versions {
-            //let versions_res: Result<Vec<FullVersion>> = resp.versions
-            resp.versions
-                .into_iter()
-                .map(|v| self.full_version(v))
-                .collect::<Result<Vec<FullVersion>, Error>>()?
-        } else {
-            let v = self.full_version(resp.versions[0].clone())?;
-            vec![v]
-        };
+        let license = resp.versions.first().and_then(|v| v.license.clone());
+        // Only the latest version is fetched unless all versions are requested.
+        let versions = resp
+            .versions
+            .into_iter()
+            .take(if all_versions { usize::MAX } else { 1 })
+            .map(|v| self.full_version(v))
+            .collect::<Result<Vec<FullVersion>, Error>>()?;
 
         let full = FullCrate {
             id: data.id,
             name: data.name,
             description: data.description,
-            license: versions[0].license.clone(),
+            license,
             documentation: data.documentation,
             homepage: data.homepage,
             repository: data.repository,
@@ -269,7 +411,7 @@ impl SyncClient {
     /// Retrieve a page of crates, optionally constrained by a query.
     ///
     /// If you want to get all results without worrying about paging,
-    /// use [`all_crates`].
+    /// use [`SyncClient::crates_iter`].
     ///
     /// # Examples
     ///
@@ -297,13 +439,95 @@ impl SyncClient {
         let mut url = self.base_url.join("crates")?;
         query.build(url.query_pairs_mut());
 
-        self.get(url)
+        self.get(url, Endpoint::Crates)
+    }
+
+    /// Get an iterator over all crates matching the given [`CratesQuery`].
+    ///
+    /// Pages are fetched lazily, starting at the page set in the query.
+    /// Seek cursors returned by the API are followed automatically.
+    ///
+    /// ```rust
+    /// # use crates_io_api::{SyncClient, CratesQuery, Error};
+    /// # fn f(client: &SyncClient) -> Result<(), Error> {
+    /// let query = CratesQuery::builder().search("http").page_size(100).build();
+    /// for krate in client.crates_iter(query).take(500) {
+    ///     println!("{}", krate?.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn crates_iter(&self, query: CratesQuery) -> CrateIter<'_> {
+        let position = query.start();
+        self.crates_iter_resume(CrateStreamCheckpoint { query, position })
+    }
+
+    /// Continue iterating over crates from a checkpoint obtained with
+    /// [`CrateIter::checkpoint`].
+    pub fn crates_iter_resume(&self, checkpoint: CrateStreamCheckpoint) -> CrateIter<'_> {
+        let query = checkpoint.query.clone();
+        let inner = PaginatedIter::resume(checkpoint.position, move |cursor| {
+            let mut query = query.clone();
+            query.set_cursor(&cursor);
+            let res = self.crates(query)?;
+            Ok(Page::from_meta(res.crates, &res.meta, &cursor))
+        });
+
+        CrateIter {
+            query: checkpoint.query,
+            inner,
+        }
     }
 
     /// Retrieves a user by username.
     pub fn user(&self, username: &str) -> Result<User, Error> {
         let url = self.base_url.join(&format!("users/{}", username))?;
-        self.get::<UserResponse>(url).map(|response| response.user)
+        self.get::<UserResponse>(url, Endpoint::User)
+            .map(|response| response.user)
+    }
+}
+
+/// Iterator over all crates matching a [`CratesQuery`].
+///
+/// Created by [`SyncClient::crates_iter`].
+pub struct CrateIter<'a> {
+    query: CratesQuery,
+    inner: PaginatedIter<'a, Crate>,
+}
+
+impl CrateIter<'_> {
+    /// The current position, for resuming the crawl later with
+    /// [`SyncClient::crates_iter_resume`].
+    pub fn checkpoint(&self) -> CrateStreamCheckpoint {
+        CrateStreamCheckpoint {
+            query: self.query.clone(),
+            position: self.inner.checkpoint(),
+        }
+    }
+
+    /// Cursor of the most recently fetched page, if any.
+    pub fn cursor(&self) -> Option<&PageCursor> {
+        self.inner.cursor()
+    }
+
+    /// Number of the most recently fetched page, if it was addressed by page
+    /// number.
+    pub fn page(&self) -> Option<u64> {
+        self.inner.page()
+    }
+
+    /// Total number of matching crates, known after the first page has been
+    /// fetched.
+    pub fn total(&self) -> Option<u64> {
+        self.inner.total()
+    }
+}
+
+impl Iterator for CrateIter<'_> {
+    type Item = Result<Crate, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
     }
 }
 
@@ -378,6 +602,81 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_crates_iter() -> Result<(), Error> {
+        let client = build_test_client();
+
+        let mut iter = client.crates_iter(CratesQuery {
+            per_page: 10,
+            ..Default::default()
+        });
+        let crates = iter.by_ref().take(15).collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(crates.len(), 15);
+        assert!(iter.total().unwrap() > 15);
+
+        let checkpoint = iter.checkpoint();
+        assert_eq!(checkpoint.position.yielded, 15);
+        let next = client.crates_iter_resume(checkpoint).next().unwrap()?;
+        assert!(crates.iter().all(|krate| krate.id != next.id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_versions() -> Result<(), Error> {
+        let client = build_test_client();
+        let krate = client.get_crate("crates_io_api")?;
+        let versions = client.crate_versions("crates_io_api")?;
+        assert_eq!(versions.len(), krate.versions.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_audit_log() -> Result<(), Error> {
+        let client = build_test_client();
+        let log = client.crate_audit_log("crates_io_api")?;
+        assert!(log
+            .iter()
+            .any(|e| e.action.action == AuditActionKind::Publish));
+        assert!(log.windows(2).all(|w| w[0].action.time <= w[1].action.time));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_reverse_dependencies_iter() -> Result<(), Error> {
+        let client = build_test_client();
+        let deps = client
+            .crate_reverse_dependencies_iter("serde", ReverseDependenciesQuery::default())
+            .take(150)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(deps.len(), 150);
+        assert!(deps.iter().all(|d| d.dependency.crate_id == "serde"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_reverse_dependencies_query() -> Result<(), Error> {
+        let client = build_test_client();
+        let query = ReverseDependenciesQuery::builder()
+            .page(2)
+            .page_size(10)
+            .kind(DependencyKind::Dev)
+            .build();
+        let deps = client
+            .crate_reverse_dependencies_iter("serde", query)
+            .take(5)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(deps
+            .iter()
+            .all(|d| d.dependency.kind == DependencyKind::Dev));
+
+        Ok(())
+    }
+
     #[test]
     fn test_crate_reverse_dependency_count() -> Result<(), Error> {
         let client = build_test_client();
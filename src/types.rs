@@ -1,16 +1,98 @@
 //! Types for the data that is available via the API.
 
+use crate::pagination::{Page, PageCursor, PaginationCheckpoint};
 use chrono::{DateTime, NaiveDate, Utc};
 use serde_derive::*;
 use std::{collections::HashMap, fmt};
 
+/// Defines an enum of known string values with a fallback for unknown ones.
+///
+/// The enum (de)serializes as the plain string.
+macro_rules! string_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $( $(#[$variant_meta:meta])* $variant:ident = $value:literal, )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+        #[serde(from = "String", into = "String")]
+        #[non_exhaustive]
+        pub enum $name {
+            $( $(#[$variant_meta])* $variant, )*
+            /// A value not known to this version of the library.
+            Unknown(String),
+        }
+
+        impl $name {
+            /// The string representation used by the API.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $( Self::$variant => $value, )*
+                    Self::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                match value.as_str() {
+                    $( $value => Self::$variant, )*
+                    _ => Self::Unknown(value),
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                Self::from(value.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                match value {
+                    $name::Unknown(value) => value,
+                    other => other.as_str().to_string(),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
 /// A list of errors returned by the API.
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct ApiErrors {
     /// Individual errors.
     pub errors: Vec<ApiError>,
 }
 
+impl ApiErrors {
+    /// Parse a response body as an API error document.
+    ///
+    /// Only bodies consisting of a non-empty `errors` list and nothing else
+    /// are considered errors.
+    pub(crate) fn from_body(body: &str) -> Option<Self> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ErrorDocument {
+            errors: Vec<ApiError>,
+        }
+
+        match serde_json::from_str::<ErrorDocument>(body) {
+            Ok(doc) if !doc.errors.is_empty() => Some(Self { errors: doc.errors }),
+            _ => None,
+        }
+    }
+}
+
 /// An error returned by the API.
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct ApiError {
@@ -29,7 +111,7 @@ impl fmt::Display for ApiError {
 }
 
 /// Used to specify the sort behaviour of the `Client::crates()` method.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Sort {
     /// Sort alphabetically.
     Alphabetical,
@@ -61,7 +143,7 @@ impl Sort {
 /// Options for the [crates]() method of the client.
 ///
 /// Used to specify pagination, sorting and a query.
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CratesQuery {
     /// Sort.
     pub(crate) sort: Sort,
@@ -80,11 +162,16 @@ pub struct CratesQuery {
     pub(crate) search: Option<String>,
     /// List of crate ids.
     pub(crate) ids: Option<Vec<String>>,
+    /// Opaque seek cursor, taking precedence over the page.
+    pub(crate) seek: Option<String>,
 }
 
 impl CratesQuery {
     pub(crate) fn build(&self, mut q: url::form_urlencoded::Serializer<'_, url::UrlQuery<'_>>) {
-        q.append_pair("page", &self.page.to_string());
+        match &self.seek {
+            Some(seek) => q.append_pair("seek", seek),
+            None => q.append_pair("page", &self.page.to_string()),
+        };
         q.append_pair("per_page", &self.per_page.to_string());
         q.append_pair("sort", self.sort.to_str());
         if let Some(id) = self.user_id {
@@ -192,6 +279,51 @@ impl CratesQuery {
     pub fn set_ids(&mut self, ids: Option<Vec<String>>) {
         self.ids = ids;
     }
+
+    /// Get a reference to the crate query's seek cursor.
+    pub fn seek(&self) -> Option<&String> {
+        self.seek.as_ref()
+    }
+
+    /// Set the crate query's seek cursor.
+    ///
+    /// If set, the page is ignored.
+    pub fn set_seek(&mut self, seek: Option<String>) {
+        self.seek = seek;
+    }
+
+    /// The position of the first page of the query.
+    pub(crate) fn start(&self) -> PaginationCheckpoint {
+        match &self.seek {
+            Some(_) => PaginationCheckpoint::start(self.cursor()),
+            None => PaginationCheckpoint::start_at(
+                self.cursor(),
+                self.page.saturating_sub(1) * self.per_page,
+            ),
+        }
+    }
+
+    pub(crate) fn cursor(&self) -> PageCursor {
+        match &self.seek {
+            Some(seek) => PageCursor::Seek(seek.clone()),
+            None => PageCursor::Page(self.page),
+        }
+    }
+
+    /// Select the page at the given cursor.
+    pub(crate) fn set_cursor(&mut self, cursor: &PageCursor) {
+        match cursor {
+            PageCursor::First => {
+                self.page = 1;
+                self.seek = None;
+            }
+            PageCursor::Page(page) => {
+                self.page = *page;
+                self.seek = None;
+            }
+            PageCursor::Seek(seek) => self.seek = Some(seek.clone()),
+        }
+    }
 }
 
 impl Default for CratesQuery {
@@ -205,10 +337,23 @@ impl Default for CratesQuery {
             category: None,
             search: None,
             ids: None,
+            seek: None,
         }
     }
 }
 
+/// Serializable position of a crate listing, for resuming long crawls.
+///
+/// See [`AsyncClient::crates_stream_resume`](crate::AsyncClient::crates_stream_resume)
+/// and [`SyncClient::crates_iter_resume`](crate::SyncClient::crates_iter_resume).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrateStreamCheckpoint {
+    /// The query of the listing.
+    pub query: CratesQuery,
+    /// The position within the listing.
+    pub position: PaginationCheckpoint,
+}
+
 /// Builder that enables easy construction of a [`CratesQuery`].
 pub struct CratesQueryBuilder {
     query: CratesQuery,
@@ -282,6 +427,15 @@ impl CratesQueryBuilder {
         self
     }
 
+    /// Opaque seek cursor, as found in [`Meta::next_page`].
+    ///
+    /// Takes precedence over the page.
+    #[must_use]
+    pub fn seek(mut self, seek: impl Into<String>) -> Self {
+        self.query.seek = Some(seek.into());
+        self
+    }
+
     /// Finalize the builder into a usable [`CratesQuery`].
     #[must_use]
     pub fn build(self) -> CratesQuery {
@@ -295,11 +449,335 @@ impl Default for CratesQueryBuilder {
     }
 }
 
+/// Options for listing the reverse dependencies of a crate.
+///
+/// The page and page size are sent to the API. The remaining options filter
+/// the received dependents on the client.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReverseDependenciesQuery {
+    /// Number of items per page.
+    pub(crate) per_page: u64,
+    /// The page to fetch.
+    pub(crate) page: u64,
+    /// Only keep dependencies of this kind.
+    pub(crate) kind: Option<DependencyKind>,
+    /// Only keep optional or non-optional dependencies.
+    pub(crate) optional: Option<bool>,
+    /// Only keep the most recent version of each dependent.
+    pub(crate) latest_only: bool,
+}
+
+impl ReverseDependenciesQuery {
+    /// Construct a new [`ReverseDependenciesQueryBuilder`].
+    pub fn builder() -> ReverseDependenciesQueryBuilder {
+        ReverseDependenciesQueryBuilder::new()
+    }
+
+    /// Get the page size.
+    pub fn page_size(&self) -> u64 {
+        self.per_page
+    }
+
+    /// Set the page size.
+    pub fn set_page_size(&mut self, per_page: u64) {
+        self.per_page = per_page;
+    }
+
+    /// Get the page.
+    pub fn page(&self) -> u64 {
+        self.page
+    }
+
+    /// Set the page.
+    pub fn set_page(&mut self, page: u64) {
+        self.page = page;
+    }
+
+    /// Get the dependency kind filter.
+    pub fn kind(&self) -> Option<&DependencyKind> {
+        self.kind.as_ref()
+    }
+
+    /// Set the dependency kind filter.
+    pub fn set_kind(&mut self, kind: Option<DependencyKind>) {
+        self.kind = kind;
+    }
+
+    /// Get the optional-ness filter.
+    pub fn optional(&self) -> Option<bool> {
+        self.optional
+    }
+
+    /// Set the optional-ness filter.
+    pub fn set_optional(&mut self, optional: Option<bool>) {
+        self.optional = optional;
+    }
+
+    /// Whether only the most recent version of each dependent is kept.
+    pub fn latest_only(&self) -> bool {
+        self.latest_only
+    }
+
+    /// Set whether only the most recent version of each dependent is kept.
+    pub fn set_latest_only(&mut self, latest_only: bool) {
+        self.latest_only = latest_only;
+    }
+
+    /// The position of the first page of the query.
+    pub(crate) fn start(&self) -> PaginationCheckpoint {
+        let page = self.page.max(1);
+        PaginationCheckpoint::start_at(PageCursor::Page(page), (page - 1) * self.per_page)
+    }
+
+    /// Whether a dependent passes the kind and optional-ness filters.
+    fn matches(&self, dep: &ReverseDependency) -> bool {
+        self.kind
+            .as_ref()
+            .is_none_or(|kind| &dep.dependency.kind == kind)
+            && self
+                .optional
+                .is_none_or(|optional| dep.dependency.optional == optional)
+    }
+
+    /// Apply the client-side filters to a single page of dependents.
+    ///
+    /// If only the latest versions are requested, the latest version of each
+    /// dependent on this page is kept.
+    pub(crate) fn filter_page(&self, deps: Vec<ReverseDependency>) -> Vec<ReverseDependency> {
+        if self.latest_only {
+            let mut filter = ReverseDependenciesFilter::new(self.clone());
+            filter.keep_latest(deps);
+            filter.take_latest()
+        } else {
+            deps.into_iter().filter(|dep| self.matches(dep)).collect()
+        }
+    }
+}
+
+/// Orders version numbers like semver without the optional `semver`
+/// dependency.
+///
+/// Pre-releases sort before the release of the same number and are compared
+/// as plain strings. Build metadata is ignored.
+fn version_order(num: &str) -> (u64, u64, u64, bool, &str) {
+    let num = num.split('+').next().unwrap_or(num);
+    let (core, pre) = match num.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (num, None),
+    };
+    let mut parts = core.split('.').map(|part| part.parse().unwrap_or(0));
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        pre.is_none(),
+        pre.unwrap_or(""),
+    )
+}
+
+/// Applies the client-side filters of a [`ReverseDependenciesQuery`] to the
+/// pages of a listing.
+///
+/// If only the latest versions are requested, the latest version of a
+/// dependent may be on any page. Dependents are then held back until the last
+/// page has been received.
+pub(crate) struct ReverseDependenciesFilter {
+    query: ReverseDependenciesQuery,
+    /// Number of dependents in the listing up to the last received page.
+    received: u64,
+    latest: Vec<ReverseDependency>,
+    index: HashMap<String, usize>,
+}
+
+impl ReverseDependenciesFilter {
+    pub(crate) fn new(query: ReverseDependenciesQuery) -> Self {
+        Self {
+            received: query.start().yielded,
+            query,
+            latest: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Filter a received page of the listing.
+    ///
+    /// The page is the last one once the received dependents add up to the
+    /// total, which also holds if the API returns fewer dependents per page
+    /// than requested.
+    pub(crate) fn page(&mut self, page: u64, res: ReverseDependencies) -> Page<ReverseDependency> {
+        let total = res.meta.total;
+        self.received += res.dependencies.len() as u64;
+        let last = res.dependencies.is_empty() || self.received >= total;
+        let items = if self.query.latest_only {
+            self.keep_latest(res.dependencies);
+            if last {
+                self.take_latest()
+            } else {
+                Vec::new()
+            }
+        } else {
+            self.query.filter_page(res.dependencies)
+        };
+
+        Page {
+            items,
+            total: Some(total),
+            next: if last {
+                None
+            } else {
+                Some(PageCursor::Page(page + 1))
+            },
+        }
+    }
+
+    /// Remember the latest version of each dependent.
+    ///
+    /// Versions are compared by number, so a backport published after a
+    /// newer release does not replace it.
+    fn keep_latest(&mut self, deps: Vec<ReverseDependency>) {
+        for dep in deps {
+            match self.index.get(&dep.crate_version.crate_name) {
+                Some(&index) => {
+                    let kept = &self.latest[index].crate_version;
+                    let newer = (version_order(&kept.num), kept.created_at)
+                        < (
+                            version_order(&dep.crate_version.num),
+                            dep.crate_version.created_at,
+                        );
+                    if newer {
+                        self.latest[index] = dep;
+                    }
+                }
+                None => {
+                    self.index
+                        .insert(dep.crate_version.crate_name.clone(), self.latest.len());
+                    self.latest.push(dep);
+                }
+            }
+        }
+    }
+
+    /// The remembered dependents that pass the filters, in order of first
+    /// appearance.
+    fn take_latest(&mut self) -> Vec<ReverseDependency> {
+        self.index.clear();
+        let query = &self.query;
+        std::mem::take(&mut self.latest)
+            .into_iter()
+            .filter(|dep| query.matches(dep))
+            .collect()
+    }
+}
+
+impl Default for ReverseDependenciesQuery {
+    fn default() -> Self {
+        Self {
+            per_page: 100,
+            page: 1,
+            kind: None,
+            optional: None,
+            latest_only: false,
+        }
+    }
+}
+
+/// Builder that enables easy construction of a [`ReverseDependenciesQuery`].
+pub struct ReverseDependenciesQueryBuilder {
+    query: ReverseDependenciesQuery,
+}
+
+impl ReverseDependenciesQueryBuilder {
+    /// Construct a new builder.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            query: ReverseDependenciesQuery::default(),
+        }
+    }
+
+    /// Set the page.
+    #[must_use]
+    pub fn page(mut self, page: u64) -> Self {
+        self.query.page = page;
+        self
+    }
+
+    /// Set the page size.
+    #[must_use]
+    pub fn page_size(mut self, size: u64) -> Self {
+        self.query.per_page = size;
+        self
+    }
+
+    /// Only keep dependencies of the given kind.
+    #[must_use]
+    pub fn kind(mut self, kind: DependencyKind) -> Self {
+        self.query.kind = Some(kind);
+        self
+    }
+
+    /// Only keep optional (`true`) or non-optional (`false`) dependencies.
+    #[must_use]
+    pub fn optional(mut self, optional: bool) -> Self {
+        self.query.optional = Some(optional);
+        self
+    }
+
+    /// Only keep the highest version of each dependent.
+    ///
+    /// As a newer version can be on any page, listings then fetch all pages
+    /// of the listing and keep every dependent in memory before yielding the
+    /// first one. For crates with many dependents, consider filtering the
+    /// versions while consuming a listing without this option instead.
+    #[must_use]
+    pub fn latest_only(mut self) -> Self {
+        self.query.latest_only = true;
+        self
+    }
+
+    /// Only keep normal, non-optional dependencies.
+    #[must_use]
+    pub fn normal_only(self) -> Self {
+        self.kind(DependencyKind::Normal).optional(false)
+    }
+
+    /// Finalize the builder into a usable [`ReverseDependenciesQuery`].
+    #[must_use]
+    pub fn build(self) -> ReverseDependenciesQuery {
+        self.query
+    }
+}
+
+impl Default for ReverseDependenciesQueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Pagination information.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct Meta {
     /// The total amount of results.
     pub total: u64,
+    /// Query string for the next page, like `?per_page=100&seek=WzEwMF0`.
+    #[serde(default)]
+    pub next_page: Option<String>,
+    /// Query string for the previous page.
+    #[serde(default)]
+    pub prev_page: Option<String>,
+}
+
+impl Meta {
+    /// The cursor of the next page, if there is one.
+    pub fn next_cursor(&self) -> Option<PageCursor> {
+        self.next_page.as_deref().and_then(PageCursor::from_query)
+    }
+
+    /// The cursor of the previous page, if there is one.
+    pub fn prev_cursor(&self) -> Option<PageCursor> {
+        self.prev_page.as_deref().and_then(PageCursor::from_query)
+    }
 }
 
 /// Links to individual API endpoints that provide crate details.
@@ -347,6 +825,21 @@ pub struct Crate {
     pub exact_match: Option<bool>,
 }
 
+#[cfg(feature = "semver")]
+impl Crate {
+    /// Parse [`Crate::max_version`] as a semver version.
+    pub fn max_semver(&self) -> Result<semver::Version, semver::Error> {
+        semver::Version::parse(&self.max_version)
+    }
+
+    /// Parse [`Crate::max_stable_version`] as a semver version.
+    pub fn max_stable_semver(&self) -> Option<Result<semver::Version, semver::Error>> {
+        self.max_stable_version
+            .as_deref()
+            .map(semver::Version::parse)
+    }
+}
+
 /// Full data for a crate listing.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(missing_docs)]
@@ -375,14 +868,27 @@ pub struct VersionLinks {
     pub version_downloads: String,
 }
 
+string_enum! {
+    /// The kind of change recorded in an [`AuditAction`].
+    pub enum AuditActionKind {
+        /// The version was published.
+        Publish = "publish",
+        /// The version was yanked.
+        Yank = "yank",
+        /// The version was unyanked.
+        Unyank = "unyank",
+    }
+}
+
 /// Changes made to a create [`Version`]
 #[derive(Serialize, Deserialize, Debug, Clone)]
-#[allow(missing_docs)]
 pub struct AuditAction {
-    /// publish, yank, unyank
-    action: String,
-    time: DateTime<Utc>,
-    user: User,
+    /// The kind of change.
+    pub action: AuditActionKind,
+    /// When the change was made.
+    pub time: DateTime<Utc>,
+    /// Who made the change.
+    pub user: User,
 }
 
 /// A [`Crate`] version.
@@ -410,6 +916,51 @@ pub struct Version {
     pub checksum: String,
 }
 
+#[cfg(feature = "semver")]
+impl Version {
+    /// Parse [`Version::num`] as a semver version.
+    pub fn semver(&self) -> Result<semver::Version, semver::Error> {
+        semver::Version::parse(&self.num)
+    }
+}
+
+/// A single entry of the audit log of a crate.
+///
+/// See [`AsyncClient::crate_audit_log`](crate::AsyncClient::crate_audit_log).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditLogEntry {
+    /// The version number the action was applied to.
+    pub version: String,
+    /// The id of the version.
+    pub version_id: u64,
+    /// The recorded action.
+    pub action: AuditAction,
+}
+
+/// Merge the audit actions of all versions into a chronological timeline.
+pub(crate) fn audit_log<'a>(versions: impl IntoIterator<Item = &'a Version>) -> Vec<AuditLogEntry> {
+    let mut log = versions
+        .into_iter()
+        .flat_map(|v| {
+            v.audit_actions.iter().map(move |action| AuditLogEntry {
+                version: v.num.clone(),
+                version_id: v.id,
+                action: action.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    log.sort_by_key(|entry| entry.action.time);
+    log
+}
+
+/// A page of versions of a crate.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[allow(missing_docs)]
+pub struct VersionsPage {
+    pub versions: Vec<Version>,
+    pub meta: Meta,
+}
+
 /// A crate category.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(missing_docs)]
@@ -443,6 +994,56 @@ pub struct CrateResponse {
     pub versions: Vec<Version>,
 }
 
+impl CrateResponse {
+    /// The audit actions of all versions, sorted chronologically.
+    pub fn audit_log(&self) -> Vec<AuditLogEntry> {
+        audit_log(&self.versions)
+    }
+}
+
+#[cfg(feature = "semver")]
+impl CrateResponse {
+    /// All versions in ascending semver order.
+    ///
+    /// Versions that are not valid semver are skipped.
+    pub fn versions_sorted_by_semver(&self) -> Vec<&Version> {
+        let mut versions = self
+            .versions
+            .iter()
+            .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+            .collect::<Vec<_>>();
+        versions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        versions.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// The highest non-yanked version matching the requirement.
+    ///
+    /// Like Cargo, pre-releases only match requirements that mention a
+    /// pre-release themselves.
+    pub fn latest_matching(&self, req: &semver::VersionReq) -> Option<&Version> {
+        self.versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+            .filter(|(semver, _)| req.matches(semver))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v)
+    }
+
+    /// The highest non-yanked version, including pre-releases.
+    ///
+    /// Use `latest_matching(&VersionReq::STAR)` to only consider stable
+    /// versions.
+    pub fn latest_non_yanked(&self) -> Option<&Version> {
+        self.versions
+            .iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| v.semver().ok().map(|semver| (semver, v)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v)
+    }
+}
+
 /// Summary for crates.io.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(missing_docs)]
@@ -490,6 +1091,16 @@ pub struct CrateDownloads {
     pub meta: CrateDownloadsMeta,
 }
 
+string_enum! {
+    /// Whether a [`User`] is an individual or a team.
+    pub enum UserKind {
+        /// An individual user.
+        User = "user",
+        /// A GitHub team.
+        Team = "team",
+    }
+}
+
 /// A crates.io user.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(missing_docs)]
@@ -497,7 +1108,7 @@ pub struct User {
     pub avatar: Option<String>,
     pub email: Option<String>,
     pub id: u64,
-    pub kind: Option<String>,
+    pub kind: Option<UserKind>,
     pub login: String,
     pub name: Option<String>,
     pub url: String,
@@ -531,6 +1142,18 @@ pub struct Owners {
     pub users: Vec<User>,
 }
 
+string_enum! {
+    /// The kind of a [`Dependency`].
+    pub enum DependencyKind {
+        /// A regular dependency.
+        Normal = "normal",
+        /// A dependency only used for tests, examples and benchmarks.
+        Dev = "dev",
+        /// A dependency of the build script.
+        Build = "build",
+    }
+}
+
 /// A crate dependency.
 /// Specifies the crate and features.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -541,13 +1164,21 @@ pub struct Dependency {
     pub downloads: u64,
     pub features: Vec<String>,
     pub id: u64,
-    pub kind: String,
+    pub kind: DependencyKind,
     pub optional: bool,
     pub req: String,
     pub target: Option<String>,
     pub version_id: u64,
 }
 
+#[cfg(feature = "semver")]
+impl Dependency {
+    /// Parse [`Dependency::req`] as a semver version requirement.
+    pub fn version_req(&self) -> Result<semver::VersionReq, semver::Error> {
+        semver::VersionReq::parse(&self.req)
+    }
+}
+
 /// List of dependencies of a crate.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[allow(missing_docs)]
@@ -582,20 +1213,19 @@ pub struct ReverseDependencies {
 impl ReverseDependencies {
     /// Fills the dependencies field from a ReverseDependenciesAsReceived struct.
     pub(crate) fn extend(&mut self, rdeps: ReverseDependenciesAsReceived) {
-        for d in rdeps.dependencies {
-            for v in &rdeps.versions {
-                if v.id == d.version_id {
-                    // Right now it iterates over the full vector for each vector element.
-                    // For large vectors, it may be faster to remove each matched element
-                    // using the drain_filter() method once it's stabilized:
-                    // https://doc.rust-lang.org/nightly/std/vec/struct.Vec.html#method.drain_filter
-                    self.dependencies.push(ReverseDependency {
-                        crate_version: v.clone(),
-                        dependency: d.clone(),
-                    });
-                }
-            }
-        }
+        let versions = rdeps
+            .versions
+            .into_iter()
+            .map(|v| (v.id, v))
+            .collect::<HashMap<_, _>>();
+
+        self.dependencies
+            .extend(rdeps.dependencies.into_iter().filter_map(|d| {
+                versions.get(&d.version_id).map(|v| ReverseDependency {
+                    crate_version: v.clone(),
+                    dependency: d,
+                })
+            }));
     }
 }
 
@@ -686,3 +1316,315 @@ pub struct FullCrate {
 pub(crate) struct UserResponse {
     pub user: User,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_string_enums_round_trip() {
+        let kinds: Vec<DependencyKind> =
+            serde_json::from_str(r#"["normal", "dev", "build", "peer"]"#).unwrap();
+        assert_eq!(
+            kinds,
+            vec![
+                DependencyKind::Normal,
+                DependencyKind::Dev,
+                DependencyKind::Build,
+                DependencyKind::Unknown("peer".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&kinds).unwrap(),
+            r#"["normal","dev","build","peer"]"#
+        );
+
+        let action: AuditAction = serde_json::from_value(serde_json::json!({
+            "action": "yank",
+            "time": "2020-01-01T00:00:00Z",
+            "user": {
+                "avatar": null,
+                "email": null,
+                "id": 1,
+                "kind": "team",
+                "login": "github:org:team",
+                "name": null,
+                "url": "",
+            },
+        }))
+        .unwrap();
+        assert_eq!(action.action, AuditActionKind::Yank);
+        assert_eq!(action.user.kind, Some(UserKind::Team));
+        assert_eq!(UserKind::from("robot").to_string(), "robot");
+    }
+
+    fn version(num: &str, yanked: bool) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "crate": "demo",
+            "created_at": "2020-01-01T00:00:00Z",
+            "updated_at": "2020-01-01T00:00:00Z",
+            "dl_path": "",
+            "downloads": 0,
+            "features": {},
+            "id": 1,
+            "num": num,
+            "yanked": yanked,
+            "license": null,
+            "readme_path": null,
+            "links": {"dependencies": "", "version_downloads": ""},
+            "crate_size": null,
+            "published_by": null,
+            "rust_version": null,
+            "checksum": "",
+        }))
+        .unwrap()
+    }
+
+    fn crate_response(versions: Vec<Version>) -> CrateResponse {
+        serde_json::from_value(serde_json::json!({
+            "categories": [],
+            "crate": {
+                "id": "demo",
+                "name": "demo",
+                "description": null,
+                "license": null,
+                "documentation": null,
+                "homepage": null,
+                "repository": null,
+                "downloads": 0,
+                "recent_downloads": null,
+                "categories": null,
+                "keywords": null,
+                "versions": null,
+                "max_version": "2.0.0-beta.1",
+                "max_stable_version": "1.10.0",
+                "links": {
+                    "owner_team": "",
+                    "owner_user": "",
+                    "owners": "",
+                    "reverse_dependencies": "",
+                    "version_downloads": "",
+                    "versions": null,
+                },
+                "created_at": "2020-01-01T00:00:00Z",
+                "updated_at": "2020-01-01T00:00:00Z",
+                "exact_match": null,
+            },
+            "keywords": [],
+            "versions": versions,
+        }))
+        .unwrap()
+    }
+
+    fn audit_action(action: &str, time: &str, login: &str) -> AuditAction {
+        serde_json::from_value(serde_json::json!({
+            "action": action,
+            "time": time,
+            "user": {
+                "avatar": null,
+                "email": null,
+                "id": 1,
+                "kind": null,
+                "login": login,
+                "name": null,
+                "url": "",
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_audit_log_is_chronological() {
+        let mut old = version("1.0.0", true);
+        old.audit_actions = vec![
+            audit_action("publish", "2020-01-01T00:00:00Z", "alice"),
+            audit_action("yank", "2022-01-01T00:00:00Z", "mallory"),
+        ];
+        let mut new = version("1.1.0", false);
+        new.audit_actions = vec![audit_action("publish", "2021-01-01T00:00:00Z", "alice")];
+        let krate = crate_response(vec![new, old]);
+
+        let log = krate
+            .audit_log()
+            .into_iter()
+            .map(|e| (e.version, e.action.action, e.action.user.login))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            log,
+            vec![
+                (
+                    "1.0.0".to_string(),
+                    AuditActionKind::Publish,
+                    "alice".to_string()
+                ),
+                (
+                    "1.1.0".to_string(),
+                    AuditActionKind::Publish,
+                    "alice".to_string()
+                ),
+                (
+                    "1.0.0".to_string(),
+                    AuditActionKind::Yank,
+                    "mallory".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "semver")]
+    fn nums(versions: &[&Version]) -> Vec<String> {
+        versions.iter().map(|v| v.num.clone()).collect()
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn test_versions_sorted_by_semver() {
+        let krate = crate_response(vec![
+            version("1.10.0", false),
+            version("2.0.0-beta.1", false),
+            version("1.9.0", false),
+            version("1.2.0", true),
+        ]);
+
+        assert_eq!(
+            nums(&krate.versions_sorted_by_semver()),
+            vec!["1.2.0", "1.9.0", "1.10.0", "2.0.0-beta.1"]
+        );
+        assert_eq!(krate.crate_data.max_semver().unwrap().major, 2);
+        assert_eq!(
+            krate.crate_data.max_stable_semver().unwrap().unwrap().minor,
+            10
+        );
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn test_latest_versions() {
+        let krate = crate_response(vec![
+            version("1.10.0", true),
+            version("2.0.0-beta.1", false),
+            version("1.9.0", false),
+            version("1.2.0", false),
+        ]);
+
+        let req = semver::VersionReq::parse("^1.2").unwrap();
+        assert_eq!(krate.latest_matching(&req).unwrap().num, "1.9.0");
+        assert_eq!(
+            krate
+                .latest_matching(&semver::VersionReq::STAR)
+                .unwrap()
+                .num,
+            "1.9.0"
+        );
+        assert_eq!(krate.latest_non_yanked().unwrap().num, "2.0.0-beta.1");
+    }
+
+    fn dependent(name: &str, num: &str, created_at: &str, kind: &str) -> ReverseDependency {
+        let mut crate_version = version(num, false);
+        crate_version.crate_name = name.to_string();
+        crate_version.created_at = created_at.parse().unwrap();
+        let dependency = serde_json::from_value(serde_json::json!({
+            "crate_id": "demo",
+            "default_features": true,
+            "downloads": 0,
+            "features": [],
+            "id": 1,
+            "kind": kind,
+            "optional": false,
+            "req": "^1",
+            "target": null,
+            "version_id": 1,
+        }))
+        .unwrap();
+        ReverseDependency {
+            crate_version,
+            dependency,
+        }
+    }
+
+    fn names(deps: &[ReverseDependency]) -> Vec<(&str, &str)> {
+        deps.iter()
+            .map(|dep| {
+                (
+                    dep.crate_version.crate_name.as_str(),
+                    dep.crate_version.num.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn rdeps(dependencies: Vec<ReverseDependency>, total: u64) -> ReverseDependencies {
+        ReverseDependencies {
+            dependencies,
+            meta: Meta {
+                total,
+                ..Meta::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_reverse_dependencies_latest_only_across_pages() {
+        let query = ReverseDependenciesQuery::builder()
+            .page_size(2)
+            .latest_only()
+            .normal_only()
+            .build();
+        let mut filter = ReverseDependenciesFilter::new(query);
+
+        let first = filter.page(
+            1,
+            rdeps(
+                vec![
+                    dependent("a", "1.0.0", "2020-01-01T00:00:00Z", "normal"),
+                    dependent("b", "2.0.0", "2021-01-01T00:00:00Z", "normal"),
+                ],
+                5,
+            ),
+        );
+        assert!(first.items.is_empty());
+        assert_eq!(first.next, Some(PageCursor::Page(2)));
+
+        let second = filter.page(
+            2,
+            rdeps(
+                vec![
+                    dependent("c", "1.0.0", "2020-01-01T00:00:00Z", "dev"),
+                    dependent("a", "1.1.0", "2021-01-01T00:00:00Z", "normal"),
+                ],
+                5,
+            ),
+        );
+        assert!(second.items.is_empty());
+
+        // A backport published after the newer release.
+        let third = filter.page(
+            3,
+            rdeps(
+                vec![dependent("b", "1.9.1", "2022-01-01T00:00:00Z", "normal")],
+                5,
+            ),
+        );
+        assert_eq!(names(&third.items), vec![("a", "1.1.0"), ("b", "2.0.0")]);
+        assert_eq!(third.next, None);
+    }
+
+    #[test]
+    fn test_reverse_dependencies_all_versions() {
+        let query = ReverseDependenciesQuery::builder().page_size(200).build();
+        let deps = vec![
+            dependent("a", "1.0.0", "2020-01-01T00:00:00Z", "normal"),
+            dependent("a", "1.1.0", "2021-01-01T00:00:00Z", "normal"),
+        ];
+        assert_eq!(
+            names(&query.filter_page(deps.clone())),
+            vec![("a", "1.0.0"), ("a", "1.1.0")]
+        );
+
+        // The API returned fewer dependents than requested.
+        let mut filter = ReverseDependenciesFilter::new(query);
+        let page = filter.page(1, rdeps(deps, 3));
+        assert_eq!(names(&page.items), vec![("a", "1.0.0"), ("a", "1.1.0")]);
+        assert_eq!(page.next, Some(PageCursor::Page(2)));
+    }
+}